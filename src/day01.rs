@@ -1,6 +1,11 @@
-use std::iter::once;
+use std::{cmp::Reverse, collections::BTreeMap, fmt::Write};
 
-use aoc_framework::*;
+use aoc_framework::{
+    anyhow::{anyhow, bail, Context},
+    *,
+};
+
+use crate::helpers::blocks;
 
 pub struct Day1;
 
@@ -21,6 +26,186 @@ impl_day!(Day1::{Part1, Part2}: 2022[1], r"
 10000
 ");
 
+/// Items carried by a single elf, with its position in the input.
+#[derive(Debug, Clone)]
+pub struct Elf {
+    pub index: usize,
+    pub items: Vec<u64>,
+}
+
+impl Elf {
+    pub fn total(&self) -> u64 {
+        self.items.iter().sum()
+    }
+}
+
+/// Per-elf calorie totals computed from the input without storing it.
+pub struct Totals<I> {
+    input: I,
+    index: usize,
+}
+
+pub fn totals<I: Iterator<Item = String>>(input: I) -> Totals<I> {
+    Totals { input, index: 0 }
+}
+
+impl<I: Iterator<Item = String>> Iterator for Totals<I> {
+    type Item = anyhow::Result<(usize, u64)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut total = None;
+        for line in self.input.by_ref() {
+            if line.is_empty() {
+                if total.is_some() {
+                    break;
+                }
+                continue;
+            }
+            match line.parse::<u64>() {
+                Ok(n) => *total.get_or_insert(0) += n,
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+        let index = self.index;
+        self.index += 1;
+        total.map(|total| Ok((index, total)))
+    }
+}
+
+/// Keeps the `k` largest totals seen so far, largest first.
+pub struct TopK {
+    k: usize,
+    top: Vec<(usize, u64)>,
+}
+
+impl TopK {
+    pub fn new(k: usize) -> Self {
+        TopK {
+            k,
+            top: Vec::with_capacity(k + 1),
+        }
+    }
+
+    pub fn push(&mut self, index: usize, total: u64) {
+        // find insertion index
+        let (Ok(ndx) | Err(ndx)) = self
+            .top
+            .binary_search_by_key(&Reverse(total), |&(_, n)| Reverse(n));
+        if ndx < self.k {
+            self.top.insert(ndx, (index, total));
+            self.top.truncate(self.k);
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<(usize, u64)> {
+        self.top
+    }
+}
+
+/// The `k` largest totals with their elf index, largest first, streaming over
+/// the input instead of parsing it into an [`Inventory`].
+pub fn top_k(input: impl Iterator<Item = String>, k: usize) -> anyhow::Result<Vec<(usize, u64)>> {
+    let mut top = TopK::new(k);
+    for elf in totals(input) {
+        let (index, total) = elf?;
+        top.push(index, total);
+    }
+    Ok(top.into_vec())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    pub elves: Vec<Elf>,
+}
+
+impl Inventory {
+    pub fn parse(input: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let elves = blocks(input)
+            .enumerate()
+            .map(|(index, block)| {
+                let items = block
+                    .iter()
+                    .map(|line| line.parse())
+                    .collect::<Result<_, _>>()
+                    .with_context(|| format!("elf {index}"))?;
+                Ok(Elf { index, items })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Inventory { elves })
+    }
+
+    /// Total carried by each elf, with its index
+    pub fn totals(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.elves.iter().map(|elf| (elf.index, elf.total()))
+    }
+
+    /// The `k` elves carrying the most calories, largest first
+    pub fn top(&self, k: usize) -> Vec<(usize, u64)> {
+        let mut top = TopK::new(k);
+        self.totals()
+            .for_each(|(index, total)| top.push(index, total));
+        top.into_vec()
+    }
+
+    /// Combined calories of the `k` elves carrying the most
+    pub fn top_sum(&self, k: usize) -> u64 {
+        self.top(k).iter().map(|&(_, total)| total).sum()
+    }
+
+    /// Nearest-rank percentile of the totals, `p` being in `0.0..=100.0`
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        if self.elves.is_empty() || !(0.0..=100.0).contains(&p) {
+            return None;
+        }
+        let mut totals = self.totals().map(|(_, total)| total).collect::<Vec<_>>();
+        totals.sort_unstable();
+        let rank = ((p / 100.0) * totals.len() as f64).ceil() as usize;
+        Some(totals[rank.clamp(1, totals.len()) - 1])
+    }
+
+    /// Number of elves per bucket of `width` calories, keyed by bucket start
+    pub fn histogram(&self, width: u64) -> anyhow::Result<BTreeMap<u64, usize>> {
+        if width == 0 {
+            bail!("Histogram buckets must be at least 1 calorie wide");
+        }
+        let mut buckets = BTreeMap::new();
+        for (_, total) in self.totals() {
+            *buckets.entry(total / width * width).or_default() += 1;
+        }
+        Ok(buckets)
+    }
+}
+
+/// Exploration commands, see `main`:
+/// - `top K`: the `K` elves carrying the most calories, streaming over the input
+/// - `percentile P`: nearest-rank percentile of the totals
+/// - `histogram WIDTH`: number of elves per bucket of `WIDTH` calories
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    let mut out = String::new();
+    match args {
+        ["top", k] => {
+            for (index, total) in top_k(input, k.parse()?)? {
+                writeln!(out, "elf {index}: {total}")?;
+            }
+        }
+        ["percentile", p] => {
+            let p = p.parse()?;
+            let total = Inventory::parse(input)?
+                .percentile(p)
+                .ok_or_else(|| anyhow!("No elves, or {p} is not in 0..=100"))?;
+            writeln!(out, "{total}")?;
+        }
+        ["histogram", width] => {
+            let width = width.parse()?;
+            for (start, count) in Inventory::parse(input)?.histogram(width)? {
+                writeln!(out, "{start}..{}: {count}", start + width)?;
+            }
+        }
+        _ => bail!("Expected `top K`, `percentile P` or `histogram WIDTH`"),
+    }
+    Ok(out)
+}
+
 pub struct Part1;
 
 impl Part for Part1 {
@@ -29,15 +214,7 @@ impl Part for Part1 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(24000));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        Ok(Num(input
-            .map(|line| line.parse::<u64>().ok())
-            // Add empty value to ensure last sum is used
-            .chain(once(None))
-            .fold((0, 0), |(max, cur), n| match n {
-                Some(n) => (max, cur + n),
-                None => (max.max(cur), 0),
-            })
-            .0))
+        Ok(Num(Inventory::parse(input)?.top_sum(1)))
     }
 }
 
@@ -49,19 +226,59 @@ impl Part for Part2 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(45000));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        input
-            .chain(once(String::new())) // Add empty line to ensure last value is used
-            .try_fold((0, Vec::with_capacity(4)), |(current, mut top), line| {
-                if !line.is_empty() {
-                    return Ok((current + line.parse::<u64>()?, top));
-                }
-                // find insertion index
-                let (Ok(ndx) | Err(ndx)) = top
-                    .binary_search_by_key(&std::cmp::Reverse(current), |&n| std::cmp::Reverse(n));
-                top.insert(ndx, current);
-                top.truncate(3);
-                Ok((0, top))
-            })
-            .map(|(_, top)| Num(top.iter().sum()))
+        Ok(Num(Inventory::parse(input)?.top_sum(3)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> impl Iterator<Item = String> {
+        Day1::EXAMPLE.trim().lines().map(String::from)
+    }
+
+    #[test]
+    fn streaming_top_matches_inventory() {
+        let inventory = Inventory::parse(example()).unwrap();
+        for k in 0..=6 {
+            assert_eq!(top_k(example(), k).unwrap(), inventory.top(k));
+        }
+        assert_eq!(inventory.top(2), [(3, 24000), (2, 11000)]);
+    }
+
+    #[test]
+    fn percentiles() {
+        let inventory = Inventory::parse(example()).unwrap();
+        assert_eq!(inventory.percentile(0.0), Some(4000));
+        assert_eq!(inventory.percentile(50.0), Some(10000));
+        assert_eq!(inventory.percentile(100.0), Some(24000));
+        assert_eq!(inventory.percentile(101.0), None);
+        assert_eq!(Inventory::default().percentile(50.0), None);
+    }
+
+    #[test]
+    fn histogram() {
+        let inventory = Inventory::parse(example()).unwrap();
+        let buckets = inventory.histogram(10000).unwrap();
+        assert_eq!(
+            buckets.into_iter().collect::<Vec<_>>(),
+            [(0, 2), (10000, 2), (20000, 1)]
+        );
+        assert!(inventory.histogram(0).is_err());
+    }
+
+    #[test]
+    fn explore_commands() {
+        assert_eq!(
+            explore(&["top", "2"], example()).unwrap(),
+            "elf 3: 24000\nelf 2: 11000\n"
+        );
+        assert_eq!(
+            explore(&["percentile", "50"], example()).unwrap(),
+            "10000\n"
+        );
+        assert!(explore(&["histogram", "0"], example()).is_err());
+        assert!(explore(&["median"], example()).is_err());
     }
 }
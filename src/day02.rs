@@ -1,6 +1,15 @@
-use std::{fmt::Debug, str::FromStr};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
 
-use aoc_framework::{anyhow::bail, *};
+use aoc_framework::{
+    anyhow::{anyhow, bail},
+    *,
+};
+
+use crate::helpers::{parse_lines, scan};
 
 pub struct Day2;
 
@@ -12,7 +21,7 @@ C Z
 
 #[repr(i8)]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
-enum RockPaperScissors {
+pub enum RockPaperScissors {
     Rock = 1,
     Paper = 2,
     Scissors = 3,
@@ -30,41 +39,17 @@ impl FromStr for RockPaperScissors {
     }
 }
 
-fn parse_tuple<L: FromStr, R: FromStr>(s: &str) -> (L, R)
-where
-    L::Err: Debug,
-    R::Err: Debug,
-{
-    let (l, r) = s.split_once(' ').unwrap();
-    (l.parse().unwrap(), r.parse().unwrap())
-}
-
-pub struct Part1;
-
-impl Part for Part1 {
-    type D = Day2;
-    const N: u8 = 1;
-    const EXAMPLE_RESULT: Option<Answer> = Some(Num(15));
+impl RockPaperScissors {
+    const ALL: [RockPaperScissors; 3] = [Self::Rock, Self::Paper, Self::Scissors];
 
-    fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        Ok(Num(input
-            .map(|line| parse_tuple(&line))
-            .map(|(l, r): (RockPaperScissors, RockPaperScissors)| {
-                r as i8
-                    + match (l as i8 - r as i8).rem_euclid(3) {
-                        0 => 3, // draw
-                        1 => 0, // loss
-                        2 => 6, // win
-                        _ => unreachable!(),
-                    }
-            })
-            .map(|v| v as u64)
-            .sum()))
+    // position in the 3-move `CyclicGame`
+    fn index(self) -> usize {
+        self as usize - 1
     }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum DesiredResult {
+pub enum DesiredResult {
     Lose,
     Draw,
     Win,
@@ -82,6 +67,351 @@ impl FromStr for DesiredResult {
     }
 }
 
+/// Game with an odd number of moves `n`, where each move beats the `n / 2`
+/// moves preceding it and loses to the `n / 2` moves following it, cyclically.
+///
+/// Rock-paper-scissors is `[rock, paper, scissors]`, and
+/// rock-paper-scissors-lizard-Spock is `[rock, Spock, paper, lizard, scissors]`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CyclicGame {
+    n: usize,
+}
+
+impl CyclicGame {
+    pub fn new(n: usize) -> anyhow::Result<Self> {
+        if n < 3 || n.is_multiple_of(2) {
+            bail!("A cyclic game needs an odd number of moves, at least 3 (got {n})");
+        }
+        Ok(CyclicGame { n })
+    }
+
+    pub fn moves(self) -> usize {
+        self.n
+    }
+
+    /// Result of playing `me` against `them`, from my point of view
+    pub fn outcome(self, me: usize, them: usize) -> DesiredResult {
+        match (me + self.n - them) % self.n {
+            0 => DesiredResult::Draw,
+            d if d <= self.n / 2 => DesiredResult::Win,
+            _ => DesiredResult::Lose,
+        }
+    }
+
+    /// A move that achieves `result` against `them`
+    pub fn response(self, them: usize, result: DesiredResult) -> usize {
+        match result {
+            DesiredResult::Lose => (them + self.n - 1) % self.n,
+            DesiredResult::Draw => them,
+            DesiredResult::Win => (them + 1) % self.n,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScoreTable {
+    /// Score for playing each move
+    pub moves: Vec<u64>,
+    /// Score for losing, drawing and winning
+    pub results: [u64; 3],
+}
+
+impl ScoreTable {
+    /// Puzzle scoring: 1 point for the first move, 2 for the second, and so
+    /// on, plus 0, 3 or 6 points for a loss, draw or win
+    pub fn standard(game: CyclicGame) -> Self {
+        ScoreTable {
+            moves: (1..=game.moves() as u64).collect(),
+            results: [0, 3, 6],
+        }
+    }
+
+    pub fn score(&self, mv: usize, result: DesiredResult) -> u64 {
+        self.moves[mv] + self.results[result as usize]
+    }
+}
+
+/// Meaning of a symbol in the second column of the strategy guide
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Response {
+    Play(usize),
+    Achieve(DesiredResult),
+}
+
+/// How to read and score a strategy guide
+#[derive(Clone, Debug)]
+pub struct Rules {
+    pub game: CyclicGame,
+    pub scores: ScoreTable,
+    pub opponent: HashMap<String, usize>,
+    pub response: HashMap<String, Response>,
+}
+
+impl Rules {
+    /// Rock-paper-scissors with `A`/`B`/`C` as the opponent's moves and
+    /// `X`/`Y`/`Z` read by `response`
+    fn rock_paper_scissors(
+        response: impl Fn(&str) -> anyhow::Result<Response>,
+    ) -> anyhow::Result<Self> {
+        let game = CyclicGame::new(3)?;
+        let opponent = ["A", "B", "C"]
+            .into_iter()
+            .map(|sym| Ok((sym.to_string(), sym.parse::<RockPaperScissors>()?.index())))
+            .collect::<anyhow::Result<_>>()?;
+        let response = ["X", "Y", "Z"]
+            .into_iter()
+            .map(|sym| Ok((sym.to_string(), response(sym)?)))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Rules {
+            game,
+            scores: ScoreTable::standard(game),
+            opponent,
+            response,
+        })
+    }
+
+    /// Move played by me and the result of a round
+    pub fn play(&self, line: &str) -> anyhow::Result<(usize, DesiredResult)> {
+        let [them, me] = scan("{} {}", line)?;
+        let them = *self
+            .opponent
+            .get(them)
+            .ok_or_else(|| anyhow!("Unknown opponent move {them:?}"))?;
+        let response = *self
+            .response
+            .get(me)
+            .ok_or_else(|| anyhow!("Unknown response {me:?}"))?;
+        Ok(match response {
+            Response::Play(me) => (me, self.game.outcome(me, them)),
+            Response::Achieve(result) => (self.game.response(them, result), result),
+        })
+    }
+
+    pub fn score(&self, input: impl Iterator<Item = String>) -> anyhow::Result<u64> {
+        parse_lines(input, |line| {
+            let (me, result) = self.play(line)?;
+            Ok(self.scores.score(me, result))
+        })
+        .sum()
+    }
+}
+
+/// Expected outcome of reading `X`/`Y`/`Z` as a given permutation of moves
+#[derive(Clone, Debug)]
+pub struct Bijection {
+    pub mapping: [RockPaperScissors; 3],
+    pub expected_score: f64,
+    /// Number of rounds lost, drawn and won
+    pub results: [u64; 3],
+}
+
+#[derive(Clone, Debug)]
+pub struct GuideReport {
+    pub rounds: u64,
+    /// How often the opponent played each move
+    pub opponent: [u64; 3],
+    /// Every `X`/`Y`/`Z`-to-move bijection, best first
+    pub bijections: Vec<Bijection>,
+    /// Best move for each of `X`/`Y`/`Z`, not necessarily a bijection
+    pub best_mapping: [RockPaperScissors; 3],
+    /// Best single move against the observed opponent distribution
+    pub best_response: RockPaperScissors,
+    /// Maximin mixed strategy over rock, paper and scissors, and the expected
+    /// score it guarantees against any opponent
+    pub mixed_strategy: ([f64; 3], f64),
+}
+
+impl Display for GuideReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} rounds, opponent played {:?}",
+            self.rounds, self.opponent
+        )?;
+        for b in &self.bijections {
+            writeln!(
+                f,
+                "X/Y/Z = {:?}: expected score {:.3} (lost {}, drew {}, won {})",
+                b.mapping, b.expected_score, b.results[0], b.results[1], b.results[2]
+            )?;
+        }
+        writeln!(f, "best mapping: {:?}", self.best_mapping)?;
+        writeln!(f, "best response: {:?}", self.best_response)?;
+        let (weights, value) = self.mixed_strategy;
+        write!(
+            f,
+            "mixed strategy: {weights:.3?}, guaranteeing {value:.3} points per round"
+        )
+    }
+}
+
+// Solves `a * x = b` in place by Gaussian elimination
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-9 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let (pivot_row, pivot_b) = (a[col].clone(), b[col]);
+        for (row, coeffs) in a.iter_mut().enumerate() {
+            if row == col {
+                continue;
+            }
+            let factor = coeffs[col] / pivot_row[col];
+            coeffs
+                .iter_mut()
+                .zip(&pivot_row)
+                .for_each(|(c, p)| *c -= factor * p);
+            b[row] -= factor * pivot_b;
+        }
+    }
+    Some((0..n).map(|i| b[i] / a[i][i]).collect())
+}
+
+/// Maximin mixed strategy of the zero-sum game with payoff `payoff[mine][theirs]`,
+/// found by enumerating supports of equal size.
+fn maximin(payoff: &[[f64; 3]; 3]) -> ([f64; 3], f64) {
+    let mut best = ([0.0; 3], f64::MIN);
+    for k in 1..=3 {
+        for rows in (0..3).combinations(k) {
+            for cols in (0..3).combinations(k) {
+                // unknowns: weight of each row in the support, then the value
+                let mut a = vec![vec![0.0; k + 1]; k + 1];
+                let mut b = vec![0.0; k + 1];
+                for (eq, &col) in cols.iter().enumerate() {
+                    for (i, &row) in rows.iter().enumerate() {
+                        a[eq][i] = payoff[row][col];
+                    }
+                    a[eq][k] = -1.0;
+                }
+                a[k][..k].fill(1.0);
+                b[k] = 1.0;
+                let Some(solution) = solve(a, b) else {
+                    continue;
+                };
+                let value = solution[k];
+                if solution[..k].iter().any(|&w| w < -1e-9) || value <= best.1 {
+                    continue;
+                }
+                let mut weights = [0.0; 3];
+                rows.iter()
+                    .zip(&solution)
+                    .for_each(|(&row, &w)| weights[row] = w.max(0.0));
+                // the opponent must not be able to do better outside of the support
+                let guaranteed = (0..3)
+                    .map(|col| {
+                        (0..3)
+                            .map(|row| weights[row] * payoff[row][col])
+                            .sum::<f64>()
+                    })
+                    .fold(f64::MAX, f64::min);
+                if guaranteed >= value - 1e-9 {
+                    best = (weights, value);
+                }
+            }
+        }
+    }
+    best
+}
+
+/// Analyzes the strategy guide, reading `X`/`Y`/`Z` as moves.
+pub fn analyze(input: impl Iterator<Item = String>) -> anyhow::Result<GuideReport> {
+    let rules = Rules::rock_paper_scissors(|sym| {
+        Ok(Response::Play(sym.parse::<RockPaperScissors>()?.index()))
+    })?;
+    // counts[opponent move][response symbol]
+    let mut counts = [[0u64; 3]; 3];
+    for round in parse_lines(input, |line| {
+        let [them, me] = scan("{} {}", line)?;
+        let Some(Response::Play(me)) = rules.response.get(me) else {
+            bail!("Unknown response {me:?}");
+        };
+        Ok((them.parse::<RockPaperScissors>()?.index(), *me))
+    }) {
+        let (them, me) = round?;
+        counts[them][me] += 1;
+    }
+    let rounds = counts.iter().flatten().sum::<u64>();
+    let opponent = counts.map(|row| row.iter().sum::<u64>());
+    let score = |mv: RockPaperScissors, them: usize| {
+        let result = rules.game.outcome(mv.index(), them);
+        (rules.scores.score(mv.index(), result), result)
+    };
+
+    let mut bijections = RockPaperScissors::ALL
+        .into_iter()
+        .permutations(3)
+        .map(|mapping| {
+            let mapping = [mapping[0], mapping[1], mapping[2]];
+            let mut total = 0;
+            let mut results = [0; 3];
+            for (them, row) in counts.iter().enumerate() {
+                for (sym, &n) in row.iter().enumerate() {
+                    let (points, result) = score(mapping[sym], them);
+                    total += n * points;
+                    results[result as usize] += n;
+                }
+            }
+            Bijection {
+                mapping,
+                expected_score: total as f64 / rounds.max(1) as f64,
+                results,
+            }
+        })
+        .collect::<Vec<_>>();
+    bijections.sort_by(|a, b| b.expected_score.total_cmp(&a.expected_score));
+
+    let best_for = |weights: [u64; 3]| {
+        RockPaperScissors::ALL
+            .into_iter()
+            .max_by_key(|&mv| {
+                (0..3)
+                    .map(|them| weights[them] * score(mv, them).0)
+                    .sum::<u64>()
+            })
+            .unwrap()
+    };
+    let best_mapping = [0, 1, 2].map(|sym| best_for(counts.map(|row| row[sym])));
+    let best_response = best_for(opponent);
+
+    let payoff = RockPaperScissors::ALL.map(|mv| [0, 1, 2].map(|them| score(mv, them).0 as f64));
+    Ok(GuideReport {
+        rounds,
+        opponent,
+        bijections,
+        best_mapping,
+        best_response,
+        mixed_strategy: maximin(&payoff),
+    })
+}
+
+/// Exploration commands, see `main`:
+/// - `analyze`: the [`GuideReport`] of the strategy guide
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    match args {
+        ["analyze"] => Ok(analyze(input)?.to_string()),
+        _ => bail!("Expected `analyze`"),
+    }
+}
+
+pub struct Part1;
+
+impl Part for Part1 {
+    type D = Day2;
+    const N: u8 = 1;
+    const EXAMPLE_RESULT: Option<Answer> = Some(Num(15));
+
+    fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
+        let rules = Rules::rock_paper_scissors(|sym| {
+            Ok(Response::Play(sym.parse::<RockPaperScissors>()?.index()))
+        })?;
+        rules.score(input).map(Num)
+    }
+}
+
 pub struct Part2;
 
 impl Part for Part2 {
@@ -90,14 +420,130 @@ impl Part for Part2 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(12));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        Ok(Num(input
-            .map(|line| parse_tuple(&line))
-            .map(|(l, r): (RockPaperScissors, DesiredResult)| match r {
-                DesiredResult::Lose => (l as i8 - 2).rem_euclid(3) + 1,
-                DesiredResult::Draw => 3 + l as i8,
-                DesiredResult::Win => 6 + (l as i8 % 3) + 1,
-            })
-            .map(|v| v as u64)
-            .sum()))
+        let rules = Rules::rock_paper_scissors(|sym| Ok(Response::Achieve(sym.parse()?)))?;
+        rules.score(input).map(Num)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // rock-paper-scissors-lizard-Spock in `CyclicGame` order
+    const ROCK: usize = 0;
+    const SPOCK: usize = 1;
+    const PAPER: usize = 2;
+    const LIZARD: usize = 3;
+    const SCISSORS: usize = 4;
+
+    #[test]
+    fn rock_paper_scissors_lizard_spock() {
+        let game = CyclicGame::new(5).unwrap();
+        let wins = [
+            (SCISSORS, PAPER),  // scissors cuts paper
+            (PAPER, ROCK),      // paper covers rock
+            (ROCK, LIZARD),     // rock crushes lizard
+            (LIZARD, SPOCK),    // lizard poisons Spock
+            (SPOCK, SCISSORS),  // Spock smashes scissors
+            (SCISSORS, LIZARD), // scissors decapitates lizard
+            (LIZARD, PAPER),    // lizard eats paper
+            (PAPER, SPOCK),     // paper disproves Spock
+            (SPOCK, ROCK),      // Spock vaporizes rock
+            (ROCK, SCISSORS),   // rock crushes scissors
+        ];
+        for me in 0..5 {
+            for them in 0..5 {
+                let expected = if me == them {
+                    DesiredResult::Draw
+                } else if wins.contains(&(me, them)) {
+                    DesiredResult::Win
+                } else {
+                    assert!(wins.contains(&(them, me)), "{me} vs {them}");
+                    DesiredResult::Lose
+                };
+                assert_eq!(game.outcome(me, them), expected, "{me} vs {them}");
+            }
+            for result in [DesiredResult::Lose, DesiredResult::Draw, DesiredResult::Win] {
+                assert_eq!(game.outcome(game.response(me, result), me), result);
+            }
+        }
+    }
+
+    #[test]
+    fn custom_symbols() {
+        let game = CyclicGame::new(5).unwrap();
+        let symbols = |names: &[(&str, usize)]| {
+            names
+                .iter()
+                .map(|&(sym, mv)| (sym.to_string(), mv))
+                .collect::<HashMap<_, _>>()
+        };
+        let opponent = symbols(&[("rock", ROCK), ("spock", SPOCK), ("lizard", LIZARD)]);
+        let mut response: HashMap<_, _> = symbols(&[("P", PAPER), ("S", SCISSORS)])
+            .into_iter()
+            .map(|(sym, mv)| (sym, Response::Play(mv)))
+            .collect();
+        response.insert("win".to_string(), Response::Achieve(DesiredResult::Win));
+        let rules = Rules {
+            game,
+            scores: ScoreTable {
+                moves: vec![0, 0, 1, 0, 2],
+                results: [0, 10, 20],
+            },
+            opponent,
+            response,
+        };
+        assert_eq!(rules.play("rock P").unwrap(), (PAPER, DesiredResult::Win));
+        assert_eq!(
+            rules.play("spock S").unwrap(),
+            (SCISSORS, DesiredResult::Lose)
+        );
+        assert_eq!(
+            rules.play("lizard win").unwrap(),
+            (SCISSORS, DesiredResult::Win)
+        );
+        let input = ["rock P", "spock S", "lizard S", "lizard win"].map(String::from);
+        assert_eq!(rules.score(input.into_iter()).unwrap(), 21 + 2 + 22 + 22);
+        assert!(rules.play("A X").is_err());
+        let err = rules
+            .score(["rock P", "paper P"].map(String::from).into_iter())
+            .unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "line 2: \"paper P\": Unknown opponent move \"paper\""
+        );
+    }
+
+    #[test]
+    fn cyclic_games_need_an_odd_number_of_moves() {
+        for n in [0, 1, 2, 4, 6, 100] {
+            assert!(CyclicGame::new(n).is_err(), "{n} moves");
+        }
+        for n in [3, 5, 101] {
+            assert_eq!(CyclicGame::new(n).unwrap().moves(), n);
+        }
+    }
+
+    #[test]
+    fn analyze_example() {
+        let input = Day2::EXAMPLE.trim().lines().map(String::from);
+        let out = explore(&["analyze"], input).unwrap();
+        let mut lines = out.lines();
+        assert_eq!(lines.next(), Some("3 rounds, opponent played [1, 1, 1]"));
+        assert_eq!(
+            lines.next(),
+            Some("X/Y/Z = [Scissors, Paper, Rock]: expected score 8.000 (lost 0, drew 0, won 3)")
+        );
+        assert!(
+            out.contains("best mapping: [Scissors, Paper, Rock]\n"),
+            "{out}"
+        );
+        // rock-paper-scissors is symmetric, so the maximin strategy is uniform
+        assert!(
+            out.ends_with(
+                "mixed strategy: [0.333, 0.333, 0.333], guaranteeing 5.000 points per round"
+            ),
+            "{out}"
+        );
     }
 }
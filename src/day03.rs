@@ -1,4 +1,11 @@
-use aoc_framework::*;
+use aoc_framework::{
+    anyhow::{anyhow, bail, Context},
+    *,
+};
+
+use std::fmt::Write;
+
+use crate::helpers::{parse_lines, Alphabet, CharSet};
 
 pub struct Day3;
 
@@ -11,15 +18,148 @@ ttgJtRGJQctTZtZT
 CrZsJsPPZsGzwwsLwLmpwMDw
 ");
 
-fn digest(bytes: &[u8]) -> u64 {
-    bytes
-        .iter()
-        .map(|b| match b {
-            b'a'..=b'z' => b - b'a',
-            b'A'..=b'Z' => b - b'A' + 26,
-            _ => panic!("Invalid input"),
+fn digest(alphabet: Alphabet, bytes: &[u8]) -> anyhow::Result<CharSet> {
+    CharSet::from_bytes(alphabet, bytes)
+}
+
+/// Priority of the only item in `common`
+fn priority(common: CharSet) -> anyhow::Result<u64> {
+    match common.len() {
+        0 => bail!("No common item"),
+        1 => Ok(common.priorities().next().unwrap()),
+        _ => bail!("Several common items: {common}"),
+    }
+}
+
+/// Rucksack split into equally sized compartments
+#[derive(Debug, Clone)]
+pub struct Rucksack {
+    pub alphabet: Alphabet,
+    pub compartments: Vec<CharSet>,
+}
+
+impl Rucksack {
+    pub fn parse(line: &str, compartments: usize, alphabet: Alphabet) -> anyhow::Result<Self> {
+        let bytes = line.as_bytes();
+        if bytes.is_empty() {
+            bail!("Empty rucksack");
+        }
+        if compartments == 0 || !bytes.len().is_multiple_of(compartments) {
+            bail!(
+                "Cannot split {} items into {compartments} equal compartments",
+                bytes.len()
+            );
+        }
+        let compartments = bytes
+            .chunks(bytes.len() / compartments)
+            .map(|items| digest(alphabet, items))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Rucksack {
+            alphabet,
+            compartments,
         })
-        .fold(0, |acc, ndx| acc | 1 << ndx)
+    }
+
+    /// Every item in the rucksack
+    pub fn contents(&self) -> CharSet {
+        self.compartments
+            .iter()
+            .fold(CharSet::new(self.alphabet), |acc, &set| acc | set)
+    }
+
+    /// Items found in every compartment
+    pub fn shared(&self) -> CharSet {
+        self.compartments
+            .iter()
+            .fold(CharSet::full(self.alphabet), |acc, &set| acc & set)
+    }
+}
+
+/// Items shared by all compartments of each rucksack
+pub fn shared_items(
+    input: impl Iterator<Item = String>,
+    compartments: usize,
+    alphabet: Alphabet,
+) -> impl Iterator<Item = anyhow::Result<CharSet>> {
+    parse_lines(input, move |line| {
+        Ok(Rucksack::parse(line, compartments, alphabet)?.shared())
+    })
+}
+
+/// Sum of the priorities of the item shared by all compartments of each rucksack
+pub fn compartment_priorities(
+    input: impl Iterator<Item = String>,
+    compartments: usize,
+    alphabet: Alphabet,
+) -> anyhow::Result<u64> {
+    parse_lines(input, |line| {
+        priority(Rucksack::parse(line, compartments, alphabet)?.shared())
+    })
+    .sum()
+}
+
+/// Items shared by every rucksack of each group of `size` elves
+pub fn group_badges(
+    input: impl Iterator<Item = String>,
+    size: usize,
+    alphabet: Alphabet,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<CharSet>>> {
+    if size == 0 {
+        bail!("Groups must have at least one elf");
+    }
+    let mut rucksacks = parse_lines(input, move |line| Rucksack::parse(line, 1, alphabet));
+    let mut group = 0;
+    Ok(std::iter::from_fn(move || {
+        let mut badges = rucksacks.next()?.map(|r| r.contents());
+        for n in 1..size {
+            let Some(rucksack) = rucksacks.next() else {
+                return Some(Err(anyhow!(
+                    "Group {} has {n} elves instead of {size}",
+                    group + 1
+                )));
+            };
+            badges = badges.and_then(|acc| Ok(acc & rucksack?.contents()));
+        }
+        group += 1;
+        Some(badges)
+    }))
+}
+
+/// Sum of the priorities of the badge of each group of `size` elves
+pub fn badge_priorities(
+    input: impl Iterator<Item = String>,
+    size: usize,
+    alphabet: Alphabet,
+) -> anyhow::Result<u64> {
+    group_badges(input, size, alphabet)?
+        .enumerate()
+        .map(|(group, badges)| {
+            badges
+                .and_then(|badges| priority(badges).with_context(|| format!("group {}", group + 1)))
+        })
+        .sum()
+}
+
+/// Exploration commands, see `main`. `ALPHABET` is one of `lowercase`, `mixed`
+/// (the default), `ascii` or `bytes`.
+/// - `shared N [ALPHABET]`: items shared by the `N` compartments of each rucksack
+/// - `badges G [ALPHABET]`: items shared by each group of `G` rucksacks
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    let (command, n, alphabet) = match args {
+        [command, n] => (*command, n.parse()?, Alphabet::MixedCase),
+        [command, n, alphabet] => (*command, n.parse()?, alphabet.parse()?),
+        _ => bail!("Expected `shared N [ALPHABET]` or `badges G [ALPHABET]`"),
+    };
+    let items: Box<dyn Iterator<Item = _>> = match command {
+        "shared" => Box::new(shared_items(input, n, alphabet)),
+        "badges" => Box::new(group_badges(input, n, alphabet)?),
+        _ => bail!("Unknown command {command:?}, expected `shared` or `badges`"),
+    };
+    let mut out = String::new();
+    for (i, items) in items.enumerate() {
+        writeln!(out, "{}: {}", i + 1, items?)?;
+    }
+    Ok(out)
 }
 
 pub struct Part1;
@@ -30,13 +170,7 @@ impl Part for Part1 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(157));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        Ok(Num(input
-            .map(|line| {
-                let (l, r) = line.as_bytes().split_at(line.len() / 2);
-                let (l, r) = (digest(l), digest(r));
-                (l & r).trailing_zeros() as u64 + 1
-            })
-            .sum()))
+        compartment_priorities(input, 2, Alphabet::MixedCase).map(Num)
     }
 }
 
@@ -48,16 +182,76 @@ impl Part for Part2 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(70));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        Ok(Num(input
-            .chunks(3)
-            .into_iter()
-            .map(|chunk| {
-                chunk
-                    .map(|line| digest(line.as_bytes()))
-                    .fold(u64::MAX, |acc, n| acc & n)
-                    .trailing_zeros() as u64
-                    + 1
-            })
-            .sum()))
+        badge_priorities(input, 3, Alphabet::MixedCase).map(Num)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(s: &str) -> impl Iterator<Item = String> + '_ {
+        s.lines().map(String::from)
+    }
+
+    #[test]
+    fn explore_lists_shared_items() {
+        let out = explore(&["shared", "2"], lines("abcAbd\nxyzXYZ")).unwrap();
+        assert_eq!(out, "1: b\n2: \n");
+        let out = explore(
+            &["badges", "3"],
+            Day3::EXAMPLE.trim().lines().map(String::from),
+        );
+        assert_eq!(out.unwrap(), "1: r\n2: Z\n");
+    }
+
+    #[test]
+    fn alphabets() {
+        assert!(explore(&["shared", "2", "mixed"], lines("a1b1")).is_err());
+        assert_eq!(
+            explore(&["shared", "2", "ascii"], lines("a1b1")).unwrap(),
+            "1: 1\n"
+        );
+        // the priority of '1' in ASCII is its code plus one
+        assert_eq!(
+            compartment_priorities(lines("a1b1"), 2, Alphabet::Ascii).unwrap(),
+            50
+        );
+    }
+
+    #[test]
+    fn rejects_empty_rucksacks_and_groups() {
+        assert!(Rucksack::parse("", 2, Alphabet::MixedCase).is_err());
+        assert!(Rucksack::parse("", 1, Alphabet::MixedCase).is_err());
+        assert!(Rucksack::parse("ab", 0, Alphabet::MixedCase).is_err());
+        assert!(Rucksack::parse("abc", 2, Alphabet::MixedCase).is_err());
+        let err = compartment_priorities(lines("aa\n\nbb"), 2, Alphabet::MixedCase).unwrap_err();
+        assert!(format!("{err:#}").contains("line 2"), "{err:#}");
+        assert!(badge_priorities(lines("a\na"), 0, Alphabet::MixedCase).is_err());
+        assert_eq!(
+            badge_priorities(lines("a\nb"), 1, Alphabet::MixedCase).unwrap(),
+            3
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line_or_group_once() {
+        let err = compartment_priorities(lines("aa\nab"), 2, Alphabet::MixedCase).unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 2: \"ab\": No common item");
+        let err = badge_priorities(lines("a\na\nb\nc"), 2, Alphabet::MixedCase).unwrap_err();
+        assert_eq!(format!("{err:#}"), "group 2: No common item");
+        let err = badge_priorities(lines("a\na\nb"), 2, Alphabet::MixedCase).unwrap_err();
+        assert_eq!(format!("{err:#}"), "Group 2 has 1 elves instead of 2");
+        let err = badge_priorities(lines("a\na\nb\n1"), 2, Alphabet::MixedCase).unwrap_err();
+        assert!(format!("{err:#}").starts_with("line 4: \"1\": "), "{err:#}");
+    }
+
+    #[test]
+    fn reports_ambiguous_priorities() {
+        let err = compartment_priorities(lines("abab"), 2, Alphabet::MixedCase).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("Several common items: ab"),
+            "{err:#}"
+        );
     }
 }
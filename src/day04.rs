@@ -1,6 +1,12 @@
-use aoc_framework::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
 
-use crate::helpers::parse_tuple;
+use aoc_framework::{
+    anyhow::{anyhow, bail},
+    *,
+};
+
+use crate::helpers::{parse_lines, parse_tuple};
 
 pub struct Day4;
 
@@ -13,8 +19,145 @@ impl_day!(Day4::{Part1, Part2}: 2022[4], r"
 2-6,4-8
 ");
 
-fn parse_range(s: &str) -> (u64, u64) {
-    parse_tuple(s, '-')
+fn parse_range(s: &str) -> anyhow::Result<(u64, u64)> {
+    let (l, r) = parse_tuple(s, '-')?;
+    if l > r {
+        bail!("Range {s:?} ends before it starts");
+    }
+    Ok((l, r))
+}
+
+type Pair = ((u64, u64), (u64, u64));
+
+fn parse_pairs(input: impl Iterator<Item = String>) -> anyhow::Result<Vec<Pair>> {
+    parse_lines(input, |line| {
+        let (a, b) = line
+            .split_once(',')
+            .ok_or_else(|| anyhow!("Expected ','"))?;
+        Ok((parse_range(a)?, parse_range(b)?))
+    })
+    .collect()
+}
+
+/// Allen relation between the first and second range of a pair. Ranges are
+/// inclusive, so two ranges meet when they are adjacent without sharing a section.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Relation {
+    Before,
+    Meets,
+    Overlaps,
+    Starts,
+    During,
+    Finishes,
+    Equals,
+    FinishedBy,
+    Contains,
+    StartedBy,
+    OverlappedBy,
+    MetBy,
+    After,
+}
+
+impl Relation {
+    pub fn classify(((al, ar), (bl, br)): Pair) -> Relation {
+        use std::cmp::Ordering::*;
+        if ar < bl {
+            return if bl - ar == 1 {
+                Relation::Meets
+            } else {
+                Relation::Before
+            };
+        } else if br < al {
+            return if al - br == 1 {
+                Relation::MetBy
+            } else {
+                Relation::After
+            };
+        }
+        match (al.cmp(&bl), ar.cmp(&br)) {
+            (Equal, Equal) => Relation::Equals,
+            (Equal, Less) => Relation::Starts,
+            (Equal, Greater) => Relation::StartedBy,
+            (Greater, Equal) => Relation::Finishes,
+            (Less, Equal) => Relation::FinishedBy,
+            (Greater, Less) => Relation::During,
+            (Less, Greater) => Relation::Contains,
+            (Less, Less) => Relation::Overlaps,
+            (Greater, Greater) => Relation::OverlappedBy,
+        }
+    }
+
+    /// One range fully contains the other
+    pub fn is_containment(self) -> bool {
+        !self.is_disjoint() && !matches!(self, Relation::Overlaps | Relation::OverlappedBy)
+    }
+
+    /// The ranges share no section
+    pub fn is_disjoint(self) -> bool {
+        matches!(
+            self,
+            Relation::Before | Relation::Meets | Relation::MetBy | Relation::After
+        )
+    }
+}
+
+fn relations(input: impl Iterator<Item = String>) -> anyhow::Result<Vec<Relation>> {
+    Ok(parse_pairs(input)?
+        .into_iter()
+        .map(Relation::classify)
+        .collect())
+}
+
+/// Number of pairs in each relation, omitting relations that never occur
+pub fn relation_counts(
+    input: impl Iterator<Item = String>,
+) -> anyhow::Result<BTreeMap<Relation, usize>> {
+    Ok(relations(input)?.into_iter().counts().into_iter().collect())
+}
+
+/// Largest number of elves assigned to a single section, with the first
+/// section reaching it, or `None` for an empty input.
+pub fn max_coverage(input: impl Iterator<Item = String>) -> anyhow::Result<Option<(usize, u64)>> {
+    // `(section, is_end)`, the last section of a range being its end
+    let mut events = Vec::new();
+    for ((al, ar), (bl, br)) in parse_pairs(input)? {
+        events.extend([(al, false), (ar, true), (bl, false), (br, true)]);
+    }
+    // starts sort before ends at the same section, as the ranges are inclusive
+    events.sort_unstable();
+    let mut elves = 0;
+    let mut best: Option<(usize, u64)> = None;
+    for (section, is_end) in events {
+        if is_end {
+            elves -= 1;
+        } else {
+            elves += 1;
+            if best.is_none_or(|(max, _)| elves > max) {
+                best = Some((elves, section));
+            }
+        }
+    }
+    Ok(best)
+}
+
+/// Exploration commands, see `main`:
+/// - `relations`: number of pairs in each relation
+/// - `coverage`: largest number of elves assigned to a single section
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    let mut out = String::new();
+    match args {
+        ["relations"] => {
+            for (relation, count) in relation_counts(input)? {
+                writeln!(out, "{relation:?}: {count}")?;
+            }
+        }
+        ["coverage"] => match max_coverage(input)? {
+            Some((elves, section)) => writeln!(out, "{elves} elves on section {section}")?,
+            None => writeln!(out, "No assignments")?,
+        },
+        _ => bail!("Expected `relations` or `coverage`"),
+    }
+    Ok(out)
 }
 
 pub struct Part1;
@@ -25,13 +168,11 @@ impl Part for Part1 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(2));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        Ok(Num(input
-            .flat_map(|line| {
-                let (a, b) = line.split_once(',')?;
-                Some((parse_range(a), parse_range(b)))
-            })
-            .filter(|((al, ar), (bl, br))| (al <= bl && ar >= br) || (bl <= al && br >= ar))
-            .count() as u64))
+        Ok(Num(relation_counts(input)?
+            .into_iter()
+            .filter(|(r, _)| r.is_containment())
+            .map(|(_, count)| count as u64)
+            .sum()))
     }
 }
 
@@ -43,18 +184,104 @@ impl Part for Part2 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(4));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        Ok(Num(input
-            .flat_map(|line| {
-                let (a, b) = line.split_once(',')?;
-                Some((parse_range(a), parse_range(b)))
-            })
-            .map(|((al, ar), (bl, br))| (al..=ar, bl..=br))
-            .filter(|(a, b)| {
-                a.contains(b.start())
-                    || a.contains(b.end())
-                    || b.contains(a.start())
-                    || b.contains(a.end())
-            })
-            .count() as u64))
+        Ok(Num(relation_counts(input)?
+            .into_iter()
+            .filter(|(r, _)| !r.is_disjoint())
+            .map(|(_, count)| count as u64)
+            .sum()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> impl Iterator<Item = String> {
+        Day4::EXAMPLE.trim().lines().map(String::from)
+    }
+
+    #[test]
+    fn classifies_every_relation() {
+        use Relation::*;
+        let cases = [
+            ((1, 2), (4, 5), Before),
+            ((1, 3), (4, 5), Meets),
+            ((1, 4), (3, 5), Overlaps),
+            ((1, 3), (1, 5), Starts),
+            ((2, 3), (1, 5), During),
+            ((3, 5), (1, 5), Finishes),
+            ((1, 5), (1, 5), Equals),
+            ((1, 5), (3, 5), FinishedBy),
+            ((1, 5), (2, 3), Contains),
+            ((1, 5), (1, 3), StartedBy),
+            ((3, 5), (1, 4), OverlappedBy),
+            ((4, 5), (1, 3), MetBy),
+            ((4, 5), (1, 2), After),
+        ];
+        for (a, b, relation) in cases {
+            assert_eq!(Relation::classify((a, b)), relation, "{a:?} {b:?}");
+        }
+    }
+
+    #[test]
+    fn ranges_up_to_the_last_section() {
+        use Relation::*;
+        let max = u64::MAX;
+        let cases = [
+            ((1, max - 1), (max, max), Meets),
+            ((1, max - 2), (max, max), Before),
+            ((max, max), (0, max - 1), MetBy),
+            ((max, max), (0, max - 2), After),
+            ((0, max), (max, max), FinishedBy),
+            ((max - 1, max), (max - 1, max), Equals),
+        ];
+        for (a, b, relation) in cases {
+            assert_eq!(Relation::classify((a, b)), relation, "{a:?} {b:?}");
+        }
+        let input = [format!("0-{max},{max}-{max}"), format!("{max}-{max},1-2")];
+        assert_eq!(max_coverage(input.into_iter()).unwrap(), Some((3, max)));
+    }
+
+    #[test]
+    fn rejects_inverted_ranges() {
+        let err = parse_pairs(["2-4,6-8", "4-2,6-8"].map(String::from).into_iter()).unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "line 2: \"4-2,6-8\": Range \"4-2\" ends before it starts"
+        );
+        assert!(parse_pairs(["2-4,9-8".to_string()].into_iter()).is_err());
+        assert!(parse_pairs(["3-3,0-0".to_string()].into_iter()).is_ok());
+    }
+
+    #[test]
+    fn example_relations() {
+        use Relation::*;
+        let counts = relation_counts(example()).unwrap();
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            [
+                (Before, 1),
+                (Meets, 1),
+                (Overlaps, 2),
+                (Finishes, 1),
+                (Contains, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn coverage() {
+        assert_eq!(max_coverage(example()).unwrap(), Some((8, 6)));
+        assert_eq!(max_coverage(std::iter::empty()).unwrap(), None);
+        // adjacent ranges do not overlap
+        let input = ["1-3,4-6", "3-3,7-9"].map(String::from);
+        assert_eq!(max_coverage(input.into_iter()).unwrap(), Some((2, 3)));
+        // but ranges sharing only their last section do
+        let input = ["1-3,3-6", "3-3,7-9"].map(String::from);
+        assert_eq!(max_coverage(input.into_iter()).unwrap(), Some((3, 3)));
+        assert_eq!(
+            explore(&["coverage"], example()).unwrap(),
+            "8 elves on section 6\n"
+        );
     }
 }
@@ -1,10 +1,16 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Write},
+    ops::Range,
+};
 
 use aoc_framework::{
-    anyhow::{anyhow, bail},
+    anyhow::{anyhow, bail, Context},
     *,
 };
 
+use crate::helpers::{parse_lines, scan_parse};
+
 pub struct Day5;
 
 impl_day!(Day5::{Part1, Part2}: 2022[5], r"
@@ -19,41 +25,292 @@ move 2 from 2 to 1
 move 1 from 1 to 2
 ");
 
-fn parse_stacks(it: &mut impl Iterator<Item = String>) -> anyhow::Result<Vec<Vec<u8>>> {
-    let mut it = it.peekable();
-    let first = it.peek().ok_or_else(|| anyhow!("Invalid input"))?;
-    let n = (first.len() + 1) / 4;
-    let mut stacks = vec![Vec::new(); n];
-    'outer: for line in it {
-        for (stack, c) in stacks.iter_mut().zip(line.bytes().skip(1).step_by(4)) {
-            match c {
-                b' ' => continue,
-                b'0'..=b'9' => break 'outer,
-                b'A'..=b'Z' => stack.push(c),
-                invalid => bail!("Invalid input: incorrect character {invalid:?}"),
+/// Stacks of crate labels, bottom first
+pub type Stacks = Vec<Vec<String>>;
+
+/// Whitespace separated fields of `line`, with their column span in characters
+fn fields(line: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    line.split(' ')
+        .scan(0, |col, field| {
+            let start = *col;
+            *col += field.chars().count() + 1;
+            Some((start..*col - 1, field))
+        })
+        .filter(|(_, field)| !field.is_empty())
+}
+
+/// Reads a crate drawing up to and including its line of stack labels.
+///
+/// Crates are `[label]` fields and belong to the stack whose label they sit
+/// above, so labels and crates may be any width and lines need not be padded.
+pub fn parse_stacks(it: &mut impl Iterator<Item = String>) -> anyhow::Result<Stacks> {
+    let mut rows = Vec::new();
+    let labels = loop {
+        let line = it
+            .next()
+            .ok_or_else(|| anyhow!("Invalid input: missing stack labels"))?;
+        if !line.trim().is_empty() && line.bytes().all(|b| b == b' ' || b.is_ascii_digit()) {
+            break line;
+        }
+        rows.push(line);
+    };
+    let columns = fields(&labels)
+        .enumerate()
+        .map(|(ndx, (span, label))| {
+            if label.parse() != Ok(ndx + 1) {
+                bail!(
+                    "Invalid input: expected stack label {}, found {label:?}",
+                    ndx + 1
+                );
             }
+            Ok(span)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // crates of each stack with the row they were drawn on, top first
+    let mut drawn = vec![Vec::new(); columns.len()];
+    for (row, line) in rows.iter().enumerate() {
+        for (span, field) in fields(line) {
+            let label = field
+                .strip_prefix('[')
+                .and_then(|f| f.strip_suffix(']'))
+                .filter(|label| !label.is_empty())
+                .ok_or_else(|| anyhow!("Invalid input: expected a crate, found {field:?}"))?;
+            let stack = columns
+                .iter()
+                .position(|col| col.start < span.end && span.start < col.end)
+                .ok_or_else(|| anyhow!("Invalid input: crate {field} is not above a stack"))?;
+            drawn[stack].push((row, label.to_string()));
         }
     }
-    stacks.iter_mut().for_each(|stack| stack.reverse());
-    Ok(stacks)
+    drawn
+        .into_iter()
+        .enumerate()
+        .map(|(ndx, crates)| {
+            let mut stack = Vec::with_capacity(crates.len());
+            for (row, label) in crates.into_iter().rev() {
+                if row + stack.len() + 1 != rows.len() {
+                    bail!(
+                        "Invalid input: crate [{label}] of stack {} is not resting on anything",
+                        ndx + 1
+                    );
+                }
+                stack.push(label);
+            }
+            Ok(stack)
+        })
+        .collect()
+}
+
+/// Draws stacks in the format [`parse_stacks`] reads, which requires crate
+/// labels without spaces. Columns are as wide as the widest crate or label, so
+/// the standard drawing comes out unchanged.
+pub fn render_stacks(stacks: &[Vec<String>]) -> String {
+    let width = stacks
+        .iter()
+        .flatten()
+        .map(|label| label.chars().count() + 2)
+        .chain([3, stacks.len().to_string().len()])
+        .max()
+        .unwrap();
+    let row = |cells: &mut dyn Iterator<Item = String>| {
+        let mut line = cells.map(|cell| format!("{cell:^width$}")).join(" ");
+        line.push('\n');
+        line
+    };
+    let height = stacks.iter().map(Vec::len).max().unwrap_or(0);
+    let mut out = String::new();
+    for level in (0..height).rev() {
+        out += &row(&mut stacks.iter().map(|stack| {
+            stack
+                .get(level)
+                .map(|label| format!("[{label}]"))
+                .unwrap_or_default()
+        }));
+    }
+    out += &row(&mut (1..=stacks.len()).map(|n| n.to_string()));
+    out
 }
 
-fn stacks_result(stacks: &[Vec<u8>]) -> String {
-    let num_stacks = stacks.len();
+fn stacks_result(stacks: &[Vec<String>]) -> String {
     stacks
         .iter()
-        .fold(String::with_capacity(num_stacks), |mut out, stack| {
-            out.extend(stack.last().map(|b| *b as char));
-            out
-        })
+        .filter_map(|stack| stack.last())
+        .map(String::as_str)
+        .collect()
 }
 
-fn parse_instruction(line: &str) -> Option<(usize, usize, usize)> {
-    line.split(' ')
-        .skip(1)
-        .step_by(2)
-        .map(|s| s.parse().unwrap())
-        .collect_tuple()
+/// A single `move N from A to B` instruction, stacks being numbered from 1
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Move {
+    pub count: usize,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from, self.to)
+    }
+}
+
+fn parse_instructions(
+    input: impl Iterator<Item = String>,
+) -> impl Iterator<Item = anyhow::Result<Move>> {
+    parse_lines(input.skip_while(|line| line.is_empty()), |line| {
+        let [count, from, to] = scan_parse("move {} from {} to {}", line)?;
+        Ok(Move { count, from, to })
+    })
+}
+
+/// Model of crane, deciding the order in which moved crates land.
+pub trait Crane {
+    /// Moves the top `count` crates of `from` onto `to`. `from` is known to
+    /// hold at least `count` crates.
+    fn lift<T>(&self, from: &mut Vec<T>, to: &mut Vec<T>, count: usize);
+}
+
+/// Moves one crate at a time, reversing their order
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn lift<T>(&self, from: &mut Vec<T>, to: &mut Vec<T>, count: usize) {
+        to.extend(from.drain(from.len() - count..).rev());
+    }
+}
+
+/// Moves all crates at once, keeping their order
+pub struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn lift<T>(&self, from: &mut Vec<T>, to: &mut Vec<T>, count: usize) {
+        to.extend(from.drain(from.len() - count..));
+    }
+}
+
+/// Runs moves with a crane, keeping every applied move so that any
+/// intermediate state can be reconstructed.
+pub struct Simulator<C> {
+    crane: C,
+    initial: Stacks,
+    stacks: Stacks,
+    log: Vec<Move>,
+}
+
+impl<C: Crane> Simulator<C> {
+    pub fn new(crane: C, stacks: Stacks) -> Self {
+        Simulator {
+            crane,
+            initial: stacks.clone(),
+            stacks,
+            log: Vec::new(),
+        }
+    }
+
+    pub fn apply(&mut self, mv: Move) -> anyhow::Result<()> {
+        Self::lift(&self.crane, &mut self.stacks, mv).with_context(|| mv.to_string())?;
+        self.log.push(mv);
+        Ok(())
+    }
+
+    fn lift(crane: &C, stacks: &mut [Vec<String>], mv: Move) -> anyhow::Result<()> {
+        let Move { count, from, to } = mv;
+        for n in [from, to] {
+            if !(1..=stacks.len()).contains(&n) {
+                bail!("No stack {n}, there are {} stacks", stacks.len());
+            }
+        }
+        let held = stacks[from - 1].len();
+        if count > held {
+            bail!("Stack {from} only holds {held} crates");
+        }
+        // moving crates onto their own stack leaves it unchanged with either model
+        if from != to {
+            let (low, high) = stacks.split_at_mut(from.max(to) - 1);
+            let (src, dst) = if from < to {
+                (&mut low[from - 1], &mut high[0])
+            } else {
+                (&mut high[0], &mut low[to - 1])
+            };
+            crane.lift(src, dst, count);
+        }
+        Ok(())
+    }
+
+    pub fn tops(&self) -> String {
+        stacks_result(&self.stacks)
+    }
+}
+
+impl<C: Crane> Simulator<C> {
+    pub fn stacks(&self) -> &[Vec<String>] {
+        &self.stacks
+    }
+
+    /// Moves applied so far, in order
+    pub fn log(&self) -> &[Move] {
+        &self.log
+    }
+
+    /// Stacks as they were after the first `step` moves
+    pub fn replay(&self, step: usize) -> anyhow::Result<Stacks> {
+        let moves = self
+            .log
+            .get(..step)
+            .ok_or_else(|| anyhow!("Only {} moves were made, no step {step}", self.log.len()))?;
+        let mut stacks = self.initial.clone();
+        for &mv in moves {
+            Self::lift(&self.crane, &mut stacks, mv)?;
+        }
+        Ok(stacks)
+    }
+
+    /// Stack diagram after the first `step` moves
+    pub fn render(&self, step: usize) -> anyhow::Result<String> {
+        Ok(render_stacks(&self.replay(step)?))
+    }
+}
+
+fn simulate<C: Crane>(
+    crane: C,
+    mut input: impl Iterator<Item = String>,
+) -> anyhow::Result<Simulator<C>> {
+    let stacks = parse_stacks(&mut input)?;
+    let mut simulator = Simulator::new(crane, stacks);
+    parse_instructions(input).try_for_each(|mv| simulator.apply(mv?))?;
+    Ok(simulator)
+}
+
+fn explore_with(
+    crane: impl Crane,
+    args: &[&str],
+    input: impl Iterator<Item = String>,
+) -> anyhow::Result<String> {
+    let simulator = simulate(crane, input)?;
+    let mut out = String::new();
+    match args {
+        ["log"] => {
+            for (i, mv) in simulator.log().iter().enumerate() {
+                writeln!(out, "{}: {mv}", i + 1)?;
+            }
+        }
+        ["replay"] => out = render_stacks(simulator.stacks()),
+        ["replay", step] => out = simulator.render(step.parse()?)?,
+        _ => bail!("Expected `log` or `replay [STEP]`"),
+    }
+    Ok(out)
+}
+
+/// Exploration commands, see `main`. `MODEL` is `9000` or `9001`.
+/// - `MODEL log`: every move, numbered from 1
+/// - `MODEL replay [STEP]`: stack drawing after the first `STEP` moves, or
+///   after all of them
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    match args {
+        ["9000", args @ ..] => explore_with(CrateMover9000, args, input),
+        ["9001", args @ ..] => explore_with(CrateMover9001, args, input),
+        _ => bail!("Expected a crane model, `9000` or `9001`"),
+    }
 }
 
 pub struct Part1;
@@ -63,17 +320,8 @@ impl Part for Part1 {
     const N: u8 = 1;
     const EXAMPLE_RESULT: Option<Answer> = Some(ConstStr("CMZ"));
 
-    fn run(mut input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let mut stacks = parse_stacks(&mut input)?;
-        input
-            .flat_map(|line| parse_instruction(&line))
-            .for_each(|(n, src, dst)| {
-                for _ in 0..n {
-                    let val = stacks[src - 1].pop();
-                    stacks[dst - 1].extend(val);
-                }
-            });
-        Ok(Str(Cow::Owned(stacks_result(&stacks))))
+    fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
+        Ok(Str(Cow::Owned(simulate(CrateMover9000, input)?.tops())))
     }
 }
 
@@ -84,20 +332,159 @@ impl Part for Part2 {
     const N: u8 = 2;
     const EXAMPLE_RESULT: Option<Answer> = Some(ConstStr("MCD"));
 
-    fn run(mut input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let mut stacks = parse_stacks(&mut input)?;
-        let mut refs = stacks.iter_mut().map(Some).collect::<Vec<_>>();
-        input
-            .flat_map(|line| parse_instruction(&line))
-            .map(|(n, srci, dsti)| (n, srci - 1, dsti - 1))
-            .for_each(|(n, srci, dsti)| {
-                let src = refs[srci].take().unwrap();
-                refs[dsti]
-                    .as_mut()
-                    .unwrap()
-                    .extend(src.drain((src.len() - n)..));
-                refs[srci] = Some(src);
-            });
-        Ok(Str(Cow::Owned(stacks_result(&stacks))))
+    fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
+        Ok(Str(Cow::Owned(simulate(CrateMover9001, input)?.tops())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> impl Iterator<Item = String> {
+        Day5::EXAMPLE
+            .strip_prefix('\n')
+            .unwrap()
+            .lines()
+            .map(String::from)
+    }
+
+    fn stacks(s: &[&str]) -> Stacks {
+        s.iter()
+            .map(|stack| stack.chars().map(String::from).collect())
+            .collect()
+    }
+
+    fn parse(drawing: &str) -> anyhow::Result<Stacks> {
+        parse_stacks(&mut drawing.lines().map(String::from))
+    }
+
+    fn labels(s: &[&[&str]]) -> Stacks {
+        s.iter()
+            .map(|stack| stack.iter().map(|label| label.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn parses_the_puzzle_drawing() {
+        let drawing = "    [D]    \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n";
+        assert_eq!(parse(drawing).unwrap(), stacks(&["ZN", "MCD", "P"]));
+        assert_eq!(render_stacks(&parse(drawing).unwrap()), drawing);
+    }
+
+    #[test]
+    fn parses_uneven_trailing_whitespace() {
+        let drawing = "    [D]\n[N] [C]        \n[Z] [M] [P] \n 1   2   3";
+        assert_eq!(parse(drawing).unwrap(), stacks(&["ZN", "MCD", "P"]));
+    }
+
+    #[test]
+    fn parses_multi_character_labels() {
+        let drawing = "      [Bob]\n[Al]  [Cy]  [X]\n 1     2     3\n";
+        let expected = labels(&[&["Al"], &["Cy", "Bob"], &["X"]]);
+        assert_eq!(parse(drawing).unwrap(), expected);
+    }
+
+    #[test]
+    fn parses_multi_digit_stack_labels() {
+        let drawing = [
+            "                                    [L]",
+            "[A] [B] [C] [D] [E] [F] [G] [H] [I] [J] [K]",
+            " 1   2   3   4   5   6   7   8   9  10  11",
+        ]
+        .join("\n");
+        let expected = stacks(&["A", "B", "C", "D", "E", "F", "G", "H", "I", "JL", "K"]);
+        assert_eq!(parse(&drawing).unwrap(), expected);
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut many: Stacks = (0..12)
+            .map(|i| (0..i % 4).map(|j| format!("C{i}x{j}")).collect())
+            .collect();
+        many[3].push("Q".into());
+        let cases = [
+            stacks(&["ZN", "MCD", "P"]),
+            stacks(&["", "A", ""]),
+            labels(&[&["long-label"], &[], &["x", "yy", "zzz"]]),
+            many,
+        ];
+        for stacks in cases {
+            let drawing = render_stacks(&stacks);
+            assert_eq!(parse(&drawing).unwrap(), stacks, "{drawing}");
+            let uneven: String = drawing
+                .lines()
+                .enumerate()
+                .map(|(i, line)| format!("{}{}\n", line.trim_end(), " ".repeat(i % 3)))
+                .collect();
+            assert_eq!(parse(&uneven).unwrap(), stacks, "{uneven}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_drawings() {
+        // floating crate
+        assert!(parse("[A]\n\n 1\n").is_err());
+        assert!(parse("    [B]\n[A]\n 1   2\n").is_err());
+        // labels out of order
+        assert!(parse("[A] [B]\n 2   1\n").is_err());
+        // crate between stacks
+        assert!(parse("  [A]\n 1      2\n").is_err());
+        assert!(parse("A\n 1\n").is_err());
+        assert!(parse("[A]\n").is_err());
+    }
+
+    #[test]
+    fn validates_moves() {
+        let mut simulator = Simulator::new(CrateMover9000, stacks(&["AB", "C"]));
+        let mv = |count, from, to| Move { count, from, to };
+        assert!(simulator.apply(mv(3, 1, 2)).is_err());
+        assert!(simulator.apply(mv(1, 0, 2)).is_err());
+        assert!(simulator.apply(mv(1, 1, 3)).is_err());
+        // rejected moves leave no trace
+        assert!(simulator.log().is_empty());
+        simulator.apply(mv(2, 1, 1)).unwrap();
+        assert_eq!(simulator.stacks(), stacks(&["AB", "C"]));
+        simulator.apply(mv(2, 1, 2)).unwrap();
+        assert_eq!(simulator.stacks(), stacks(&["", "CBA"]));
+        assert_eq!(simulator.log().len(), 2);
+    }
+
+    #[test]
+    fn crane_models() {
+        let mut input = example();
+        let initial = parse_stacks(&mut input).unwrap();
+        let moves: Vec<_> = parse_instructions(input).map(Result::unwrap).collect();
+        let mut old = Simulator::new(CrateMover9000, initial.clone());
+        let mut new = Simulator::new(CrateMover9001, initial);
+        for &mv in &moves {
+            old.apply(mv).unwrap();
+            new.apply(mv).unwrap();
+        }
+        assert_eq!(old.tops(), "CMZ");
+        assert_eq!(new.tops(), "MCD");
+    }
+
+    #[test]
+    fn replays_any_step() {
+        let simulator = simulate(CrateMover9000, example()).unwrap();
+        assert_eq!(simulator.replay(0).unwrap(), simulator.initial);
+        assert_eq!(simulator.replay(4).unwrap(), simulator.stacks());
+        assert!(simulator.replay(5).is_err());
+        assert_eq!(
+            simulator.render(1).unwrap(),
+            "[D]        \n[N] [C]    \n[Z] [M] [P]\n 1   2   3 \n"
+        );
+    }
+
+    #[test]
+    fn explore_commands() {
+        let log = explore(&["9001", "log"], example()).unwrap();
+        assert_eq!(log.lines().next(), Some("1: move 1 from 2 to 1"));
+        assert_eq!(log.lines().count(), 4);
+        let last = explore(&["9001", "replay"], example()).unwrap();
+        assert_eq!(last, explore(&["9001", "replay", "4"], example()).unwrap());
+        assert!(last.starts_with("        [D]\n"), "{last}");
+        assert!(explore(&["9002", "log"], example()).is_err());
     }
 }
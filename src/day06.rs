@@ -1,26 +1,77 @@
-use anyhow::anyhow;
+use std::io::{self, BufReader, Read};
+
+use anyhow::{anyhow, bail};
 use aoc_framework::*;
 
 pub struct Day6;
 
 impl_day!(Day6::{Part1, Part2}: 2022[6], r"mjqjpqmgbljsphdztnvjfqwrcgsmlb");
 
-// faster than Itertools::all_unique
-fn all_unique(chunk: &[u8]) -> bool {
-    chunk
-        .iter()
-        .fold(0u32, |mask, b| mask | (1 << (*b - b'a') as usize))
-        .count_ones() as usize
-        == chunk.len()
+/// Positions, counted in bytes read, at which the last `window` bytes of a
+/// stream were all different. Each byte is handled in constant time by
+/// keeping a count of every byte value inside the window.
+pub struct Markers<R> {
+    bytes: io::Bytes<BufReader<R>>,
+    window: Vec<u8>,
+    counts: [u32; 256],
+    // byte values appearing more than once in the window
+    repeated: usize,
+    position: usize,
+}
+
+impl<R: Read> Markers<R> {
+    pub fn new(reader: R, window: usize) -> anyhow::Result<Self> {
+        if window == 0 {
+            bail!("Marker window must not be empty");
+        }
+        Ok(Markers {
+            bytes: BufReader::new(reader).bytes(),
+            window: vec![0; window],
+            counts: [0; 256],
+            repeated: 0,
+            position: 0,
+        })
+    }
+}
+
+impl<R: Read> Iterator for Markers<R> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let byte = match self.bytes.next()? {
+                Ok(byte) => byte,
+                Err(e) => return Some(Err(e)),
+            };
+            let size = self.window.len();
+            let slot = &mut self.window[self.position % size];
+            if self.position >= size {
+                let count = &mut self.counts[*slot as usize];
+                *count -= 1;
+                if *count == 1 {
+                    self.repeated -= 1;
+                }
+            }
+            *slot = byte;
+            let count = &mut self.counts[byte as usize];
+            *count += 1;
+            if *count == 2 {
+                self.repeated += 1;
+            }
+            self.position += 1;
+            if self.position >= size && self.repeated == 0 {
+                return Some(Ok(self.position));
+            }
+        }
+    }
 }
 
-fn first_unique_chunk<const N: usize>(s: &str) -> anyhow::Result<Answer> {
-    s.as_bytes()
-        .windows(N)
-        .enumerate()
-        .find(|(_, chunk)| all_unique(chunk))
-        .map(|(i, _)| Num(i as u64 + N as u64))
-        .ok_or_else(|| anyhow!("No solution found"))
+fn first_marker(mut input: impl Iterator<Item = String>, window: usize) -> anyhow::Result<Answer> {
+    let signal = input.next().ok_or_else(|| anyhow!("Invalid input"))?;
+    match Markers::new(signal.as_bytes(), window)?.next() {
+        Some(position) => Ok(Num(position? as u64)),
+        None => bail!("No solution found"),
+    }
 }
 
 pub struct Part1;
@@ -30,8 +81,8 @@ impl Part for Part1 {
     const N: u8 = 1;
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(7));
 
-    fn run(mut input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        first_unique_chunk::<4>(&input.next().ok_or_else(|| anyhow!("Invalid input"))?)
+    fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
+        first_marker(input, 4)
     }
 }
 
@@ -42,7 +93,101 @@ impl Part for Part2 {
     const N: u8 = 2;
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(19));
 
-    fn run(mut input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        first_unique_chunk::<14>(&input.next().ok_or_else(|| anyhow!("Invalid input"))?)
+    fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
+        first_marker(input, 14)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers(input: impl Read, window: usize) -> Vec<usize> {
+        Markers::new(input, window)
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    /// Every marker position found by checking each window from scratch
+    fn naive(input: &[u8], window: usize) -> Vec<usize> {
+        (window..=input.len())
+            .filter(|&end| {
+                let bytes = &input[end - window..end];
+                (0..window).all(|i| !bytes[i + 1..].contains(&bytes[i]))
+            })
+            .collect()
+    }
+
+    /// Reader handing out at most `chunk` bytes per call
+    struct Chunked<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn every_marker() {
+        let signal = b"mjqjpqmgbljsphdztnvjfqwrcgsmlb";
+        // every window of 4 is a marker once the first one is found
+        let all: Vec<usize> = (7..=30).collect();
+        assert_eq!(markers(&signal[..], 4), all);
+        for window in 1..=16 {
+            assert_eq!(markers(&signal[..], window), naive(signal, window));
+        }
+        assert_eq!(markers(&b"aaaa"[..], 1), [1, 2, 3, 4]);
+        assert_eq!(markers(&b"aaaa"[..], 2), []);
+    }
+
+    #[test]
+    fn any_byte() {
+        let signal = [0, 255, 0, b'\n', 200, 200, 1, 2];
+        assert_eq!(markers(&signal[..], 3), [4, 5, 8]);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(markers(&all[..], 256), [256]);
+        assert_eq!(markers("aé€😀".as_bytes(), 10), [10]);
+    }
+
+    #[test]
+    fn input_split_across_reads() {
+        let mut seed: u64 = 7;
+        let signal: Vec<u8> = (0..10_000)
+            .map(|_| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                b'a' + (seed >> 33) as u8 % 20
+            })
+            .collect();
+        for chunk in [1, 2, 3, 7, 4096] {
+            for window in [4, 14] {
+                let reader = Chunked {
+                    data: &signal,
+                    chunk,
+                };
+                assert_eq!(
+                    markers(reader, window),
+                    naive(&signal, window),
+                    "chunks of {chunk}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn window_longer_than_input() {
+        assert_eq!(markers(&b"abc"[..], 4), []);
+        assert_eq!(markers(&b""[..], 14), []);
+        assert!(Markers::new(&b"abc"[..], 0).is_err());
+        let input = ["abc".to_string()].into_iter();
+        assert!(Part2::run(input).is_err());
     }
 }
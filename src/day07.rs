@@ -1,4 +1,15 @@
-use aoc_framework::{anyhow::anyhow, *};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Write},
+    ops::RangeBounds,
+};
+
+use aoc_framework::{
+    anyhow::{anyhow, bail},
+    *,
+};
+
+use crate::helpers::parse_lines;
 
 pub struct Day7;
 
@@ -28,45 +39,284 @@ $ ls
 7214296 k
 ");
 
-fn build_fs_map(input: impl Iterator<Item = String>) -> anyhow::Result<Vec<u64>> {
-    // current "path"
-    let mut stack = Vec::new();
-    // sizes of processed directories
-    let mut directories = Vec::new();
-    for line in input {
-        if let Some(dir) = line.strip_prefix("$ cd ") {
-            if dir == ".." {
-                // pop current directory, add its size to parent
-                let size = stack.pop().unwrap();
-                if let Some(prev) = stack.last_mut() {
-                    *prev += size;
-                }
-                // push it to the result
-                directories.push(size);
+#[derive(Debug)]
+enum Kind {
+    Dir(BTreeMap<String, usize>),
+    File(u64),
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    parent: usize,
+    kind: Kind,
+}
+
+/// Directory tree rebuilt from a terminal log. Nodes live in an arena with the
+/// root at index 0, and every node comes after its parent.
+#[derive(Debug)]
+pub struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+const ROOT: usize = 0;
+
+impl FileSystem {
+    pub fn parse(input: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut fs = FileSystem {
+            nodes: vec![Node {
+                name: String::new(),
+                parent: ROOT,
+                kind: Kind::Dir(BTreeMap::new()),
+            }],
+        };
+        let mut cwd = ROOT;
+        parse_lines(input, |line| {
+            if let Some(path) = line.strip_prefix("$ cd ") {
+                cwd = fs.resolve(cwd, path)?;
+            } else if line == "$ ls" {
+                // listings only add entries, so repeating one is harmless
+            } else if let Some(name) = line.strip_prefix("dir ") {
+                fs.entry(cwd, name, Kind::Dir(BTreeMap::new()))?;
             } else {
-                stack.push(0);
+                let (size, name) = line
+                    .split_once(' ')
+                    .ok_or_else(|| anyhow!("Invalid input"))?;
+                fs.entry(cwd, name, Kind::File(size.parse()?))?;
+            }
+            Ok(())
+        })
+        .collect::<anyhow::Result<()>>()?;
+        Ok(fs)
+    }
+
+    fn children(&self, dir: usize) -> anyhow::Result<&BTreeMap<String, usize>> {
+        match &self.nodes[dir].kind {
+            Kind::Dir(children) => Ok(children),
+            Kind::File(_) => bail!("{} is not a directory", self.path(dir)),
+        }
+    }
+
+    /// Node at `path`, relative to `cwd` unless it starts with `/`. Directories
+    /// that were never listed are created on the way.
+    fn resolve(&mut self, cwd: usize, path: &str) -> anyhow::Result<usize> {
+        let (mut node, path) = match path.strip_prefix('/') {
+            Some(rest) => (ROOT, rest),
+            None => (cwd, path),
+        };
+        for name in path
+            .split('/')
+            .filter(|name| !name.is_empty() && *name != ".")
+        {
+            node = match (name, self.children(node)?.get(name)) {
+                ("..", _) => self.nodes[node].parent,
+                (_, Some(&child)) => {
+                    self.children(child)?;
+                    child
+                }
+                (_, None) => self.entry(node, name, Kind::Dir(BTreeMap::new()))?,
+            };
+        }
+        Ok(node)
+    }
+
+    /// Returns the child `name` of `dir`, adding it if it is new. Seeing an
+    /// entry again is fine as long as it agrees with what was seen before.
+    fn entry(&mut self, dir: usize, name: &str, kind: Kind) -> anyhow::Result<usize> {
+        if let Some(&child) = self.children(dir)?.get(name) {
+            match (&self.nodes[child].kind, &kind) {
+                (Kind::Dir(_), Kind::Dir(_)) => {}
+                (Kind::File(old), Kind::File(new)) if old == new => {}
+                _ => bail!("{} listed twice with different contents", self.path(child)),
             }
-        } else if line == "$ ls" || line.starts_with("dir") {
-            // no-op
-        } else {
-            // file entry, parse its size
-            let size: u64 = line
-                .split_once(' ')
-                .ok_or_else(|| anyhow!("Invalid input"))?
-                .0
-                .parse()?;
-            // add size to size of current directory
-            *stack.last_mut().unwrap() += size;
+            return Ok(child);
+        }
+        let child = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: dir,
+            kind,
+        });
+        if let Kind::Dir(children) = &mut self.nodes[dir].kind {
+            children.insert(name.to_string(), child);
+        }
+        Ok(child)
+    }
+
+    /// Absolute path of a node
+    pub fn path(&self, mut node: usize) -> String {
+        let mut names = Vec::new();
+        while node != ROOT {
+            names.push(self.nodes[node].name.as_str());
+            node = self.nodes[node].parent;
+        }
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    /// Total size of every node, indexed like the arena
+    fn sizes(&self) -> Vec<u64> {
+        let mut sizes: Vec<u64> = self
+            .nodes
+            .iter()
+            .map(|node| match node.kind {
+                Kind::File(size) => size,
+                Kind::Dir(_) => 0,
+            })
+            .collect();
+        // children come after their parent, so they are complete when reached
+        for node in (1..self.nodes.len()).rev() {
+            sizes[self.nodes[node].parent] += sizes[node];
+        }
+        sizes
+    }
+
+    /// Total size of each directory, with its path
+    pub fn directories(&self) -> impl Iterator<Item = (String, u64)> + '_ {
+        self.sizes()
+            .into_iter()
+            .enumerate()
+            .filter(|&(node, _)| matches!(self.nodes[node].kind, Kind::Dir(_)))
+            .map(|(node, size)| (self.path(node), size))
+    }
+
+    pub fn used(&self) -> u64 {
+        self.sizes()[ROOT]
+    }
+}
+
+impl FileSystem {
+    /// Directory totals, largest first, like `du | sort -rn`
+    pub fn du(&self) -> Vec<(String, u64)> {
+        let mut dirs: Vec<_> = self.directories().collect();
+        dirs.sort_by(|(a_path, a), (b_path, b)| b.cmp(a).then_with(|| a_path.cmp(b_path)));
+        dirs
+    }
+
+    /// Files and directories whose name matches the `glob` (`*` and `?`
+    /// wildcards) and whose total size is in `size`, in path order
+    pub fn find(&self, glob: &str, size: impl RangeBounds<u64>) -> Vec<(String, u64)> {
+        let mut found: Vec<_> = self
+            .sizes()
+            .into_iter()
+            .enumerate()
+            .filter(|(node, total)| {
+                size.contains(total)
+                    && glob_match(glob.as_bytes(), self.nodes[*node].name.as_bytes())
+            })
+            .map(|(node, total)| (self.path(node), total))
+            .collect();
+        found.sort();
+        found
+    }
+
+    /// Smallest directory to delete so that a disk of `capacity` bytes has
+    /// `needed` bytes free, or `None` when there is already enough room.
+    pub fn plan_deletion(
+        &self,
+        capacity: u64,
+        needed: u64,
+    ) -> anyhow::Result<Option<(String, u64)>> {
+        let free = capacity
+            .checked_sub(self.used())
+            .ok_or_else(|| anyhow!("Using {} bytes on a disk of {capacity}", self.used()))?;
+        let Some(missing) = needed.checked_sub(free).filter(|&missing| missing > 0) else {
+            return Ok(None);
+        };
+        self.directories()
+            .filter(|&(_, size)| size >= missing)
+            .min_by_key(|&(_, size)| size)
+            .map(Some)
+            .ok_or_else(|| anyhow!("Cannot free {needed} bytes on a disk of {capacity}"))
+    }
+}
+
+/// Matches `name` against a pattern where `*` stands for any run of
+/// characters and `?` for any single one.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    // position of the last `*` and of the name where it started matching
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // let the last `*` swallow one more character
+                Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    p = sp + 1;
+                    n = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Indented listing in the style of the puzzle statement
+impl Display for FileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sizes = self.sizes();
+        let mut todo = vec![(ROOT, 0)];
+        while let Some((node, depth)) = todo.pop() {
+            let Node { name, kind, .. } = &self.nodes[node];
+            let name = if node == ROOT { "/" } else { name };
+            let kind = match kind {
+                Kind::Dir(children) => {
+                    todo.extend(children.values().rev().map(|&child| (child, depth + 1)));
+                    "dir"
+                }
+                Kind::File(_) => "file",
+            };
+            writeln!(
+                f,
+                "{:indent$}- {name} ({kind}, size={})",
+                "",
+                sizes[node],
+                indent = 2 * depth
+            )?;
         }
+        Ok(())
     }
-    // pop remaining directories, add their size to their parent each time
-    while let Some(val) = stack.pop() {
-        if let Some(prev) = stack.last_mut() {
-            *prev += val;
+}
+
+/// Exploration commands, see `main`:
+/// - `tree`: indented listing of the disk with sizes
+/// - `du`: directory totals, largest first
+/// - `find GLOB [MIN [MAX]]`: files and directories matching `GLOB` whose total
+///   size is within `MIN..=MAX`
+/// - `free CAPACITY NEEDED`: smallest directory to delete to get `NEEDED` bytes
+///   free on a disk of `CAPACITY` bytes
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    let fs = FileSystem::parse(input)?;
+    let mut out = String::new();
+    let found = match args {
+        ["tree"] => return Ok(fs.to_string()),
+        ["du"] => fs.du(),
+        ["find", glob] => fs.find(glob, ..),
+        ["find", glob, min] => fs.find(glob, min.parse::<u64>()?..),
+        ["find", glob, min, max] => fs.find(glob, min.parse::<u64>()?..=max.parse()?),
+        ["free", capacity, needed] => {
+            match fs.plan_deletion(capacity.parse()?, needed.parse()?)? {
+                Some((path, size)) => writeln!(out, "delete {path} to free {size} bytes")?,
+                None => writeln!(out, "nothing to delete")?,
+            }
+            return Ok(out);
         }
-        directories.push(val);
+        _ => bail!("Expected `tree`, `du`, `find GLOB [MIN [MAX]]` or `free CAPACITY NEEDED`"),
+    };
+    for (path, size) in found {
+        writeln!(out, "{size}\t{path}")?;
     }
-    Ok(directories)
+    Ok(out)
 }
 
 pub struct Part1;
@@ -77,8 +327,12 @@ impl Part for Part1 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(95437));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let fs = build_fs_map(input)?;
-        Ok(Num(fs.into_iter().filter(|size| *size < 100_000).sum()))
+        let fs = FileSystem::parse(input)?;
+        Ok(Num(fs
+            .directories()
+            .map(|(_, size)| size)
+            .filter(|size| *size < 100_000)
+            .sum()))
     }
 }
 
@@ -90,13 +344,186 @@ impl Part for Part2 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(24933642));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let fs = build_fs_map(input)?;
-        let total_used = fs.last().unwrap();
-        let required = 30_000_000 - (70_000_000 - total_used);
-        Ok(Num(fs
-            .into_iter()
-            .filter(|dir| *dir > required)
-            .min()
-            .unwrap()))
+        let fs = FileSystem::parse(input)?;
+        let plan = fs.plan_deletion(70_000_000, 30_000_000)?;
+        Ok(Num(plan.map_or(0, |(_, size)| size)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> FileSystem {
+        FileSystem::parse(Day7::EXAMPLE.trim().lines().map(String::from)).unwrap()
+    }
+
+    fn entries(found: &[(&str, u64)]) -> Vec<(String, u64)> {
+        found
+            .iter()
+            .map(|&(path, size)| (path.to_string(), size))
+            .collect()
+    }
+
+    #[test]
+    fn tree() {
+        let tree = example().to_string();
+        let expected = [
+            "- / (dir, size=48381165)",
+            "  - a (dir, size=94853)",
+            "    - e (dir, size=584)",
+            "      - i (file, size=584)",
+            "    - f (file, size=29116)",
+        ];
+        assert_eq!(tree.lines().take(5).collect::<Vec<_>>(), expected);
+        assert_eq!(tree.lines().last(), Some("    - k (file, size=7214296)"));
+        assert_eq!(tree.lines().count(), 14);
+    }
+
+    #[test]
+    fn du() {
+        let expected = [
+            ("/", 48381165),
+            ("/d", 24933642),
+            ("/a", 94853),
+            ("/a/e", 584),
+        ];
+        assert_eq!(example().du(), entries(&expected));
+    }
+
+    #[test]
+    fn find() {
+        let fs = example();
+        assert_eq!(fs.find("*.txt", ..), entries(&[("/b.txt", 14848514)]));
+        assert_eq!(
+            fs.find("?", ..1000),
+            entries(&[("/a/e", 584), ("/a/e/i", 584)])
+        );
+        assert_eq!(
+            fs.find("d*", 8_000_000..),
+            entries(&[("/d", 24933642), ("/d/d.log", 8033020)])
+        );
+        assert!(fs.find("*.*", 0..=0).is_empty());
+    }
+
+    #[test]
+    fn glob() {
+        let matches = |pattern: &str, name: &str| glob_match(pattern.as_bytes(), name.as_bytes());
+        assert!(matches("*", ""));
+        assert!(matches("a*b*c", "aXbYbc"));
+        assert!(matches("??.*", "ab.txt"));
+        assert!(!matches("??", "abc"));
+        assert!(!matches("*.log", "d.log.bak"));
+    }
+
+    #[test]
+    fn plan_deletion() {
+        let fs = example();
+        let plan = fs.plan_deletion(70_000_000, 30_000_000).unwrap();
+        assert_eq!(plan, Some(("/d".to_string(), 24933642)));
+        // a directory exactly as large as needed is enough
+        let plan = fs.plan_deletion(48381165, 94853).unwrap();
+        assert_eq!(plan, Some(("/a".to_string(), 94853)));
+        assert_eq!(fs.plan_deletion(100_000_000, 1000).unwrap(), None);
+        assert!(fs.plan_deletion(1000, 0).is_err());
+        // deleting `/` frees the whole disk, but no more
+        let plan = fs.plan_deletion(50_000_000, 50_000_000).unwrap();
+        assert_eq!(plan, Some(("/".to_string(), 48381165)));
+        assert!(fs.plan_deletion(50_000_000, 50_000_001).is_err());
+    }
+
+    fn parse(log: &str) -> anyhow::Result<FileSystem> {
+        FileSystem::parse(log.trim().lines().map(String::from))
+    }
+
+    fn sizes(fs: &FileSystem) -> Vec<(String, u64)> {
+        let mut dirs: Vec<_> = fs.directories().collect();
+        dirs.sort();
+        dirs
+    }
+
+    #[test]
+    fn cd_root_mid_log() {
+        let fs = parse("$ cd /\n$ cd x\n$ ls\n10 f\n$ cd /\n$ ls\ndir x\n20 g").unwrap();
+        let expected = [("/", 30), ("/x", 10)];
+        assert_eq!(sizes(&fs), entries(&expected));
+    }
+
+    #[test]
+    fn revisited_directories() {
+        let log = "
+$ cd /
+$ ls
+dir a
+$ cd a
+$ ls
+100 f
+$ cd ..
+$ cd a
+$ ls
+100 f
+200 g
+$ cd ..
+$ ls
+dir a
+";
+        let fs = parse(log).unwrap();
+        assert_eq!(sizes(&fs), entries(&[("/", 300), ("/a", 300)]));
+        // the same listing twice counts once
+        let twice = "$ cd /\n$ ls\n5 f\ndir a\n$ ls\n5 f\ndir a";
+        assert_eq!(parse(twice).unwrap().used(), 5);
+        assert_eq!(example().used(), 48381165);
+        assert!(parse("$ cd /\n$ ls\n5 f\n$ ls\n6 f").is_err());
+    }
+
+    #[test]
+    fn absolute_paths() {
+        let log = "
+$ cd /a/b
+$ ls
+7 f
+$ cd /a/./c/
+$ ls
+3 g
+$ cd /a/c/../b
+$ ls
+7 f
+";
+        let fs = parse(log).unwrap();
+        let expected = [("/", 10), ("/a", 10), ("/a/b", 7), ("/a/c", 3)];
+        assert_eq!(sizes(&fs), entries(&expected));
+        // going up from the root stays there
+        assert_eq!(parse("$ cd /..\n$ ls\n1 f").unwrap().path(1), "/f");
+    }
+
+    #[test]
+    fn files_and_directories_with_the_same_name() {
+        let fs = parse("$ cd /\n$ ls\n4 x\ndir y\n$ cd y\n$ ls\ndir x\n$ cd x\n$ ls\n2 y").unwrap();
+        assert_eq!(
+            fs.find("?", ..),
+            entries(&[("/x", 4), ("/y", 2), ("/y/x", 2), ("/y/x/y", 2)])
+        );
+        let err = parse("$ cd /\n$ ls\n4 x\ndir x").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("line 4: \"dir x\": /x listed twice"),
+            "{err:#}"
+        );
+        let err = parse("$ cd /\n$ ls\n4 x\n$ cd x").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("/x is not a directory"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn explore_commands() {
+        let input = || Day7::EXAMPLE.trim().lines().map(String::from);
+        let out = explore(&["free", "70000000", "30000000"], input()).unwrap();
+        assert_eq!(out, "delete /d to free 24933642 bytes\n");
+        assert_eq!(
+            explore(&["find", "*.dat", "0", "10000000"], input()).unwrap(),
+            "8504156\t/c.dat\n"
+        );
+        assert!(explore(&["find"], input()).is_err());
     }
 }
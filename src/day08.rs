@@ -1,4 +1,8 @@
-use aoc_framework::*;
+use std::{fmt::Write, str::FromStr};
+
+use aoc_framework::{anyhow::bail, *};
+
+use crate::helpers::parse_lines;
 
 pub struct Day8;
 
@@ -10,8 +14,6 @@ impl_day!(Day8::{Part1, Part2}: 2022[8], r"
 35390
 ");
 
-const DELTAS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
-
 struct Grid {
     data: Vec<u8>,
     w: usize,
@@ -19,63 +21,63 @@ struct Grid {
 }
 
 impl Grid {
-    fn parse(input: impl Iterator<Item = String>) -> Grid {
-        let mut input = input.peekable();
-        let w = input.peek().map(|l| l.len()).unwrap_or(0);
-        let data = input.fold(Vec::new(), |mut data, l| {
-            data.extend(l.bytes().map(|b| b - b'0'));
-            data
-        });
-        let h = data.len() / w;
-        Grid { data, w, h }
-    }
-
-    fn get(&self, x: isize, y: isize) -> Option<u8> {
-        if x < 0 || y < 0 || x as usize >= self.w || y as usize >= self.h {
-            return None;
-        }
-        self.data.get(y as usize * self.w + x as usize).copied()
+    /// Rows of digits, all of the same width. No rows make an empty grid.
+    fn parse(input: impl Iterator<Item = String>) -> anyhow::Result<Grid> {
+        let mut w = None;
+        let data = parse_lines(input, |line| {
+            let w = *w.get_or_insert(line.len());
+            if line.is_empty() {
+                bail!("Empty row of trees");
+            }
+            if line.len() != w {
+                bail!("Expected {w} trees, found {}", line.len());
+            }
+            line.bytes()
+                .map(|b| match b {
+                    b'0'..=b'9' => Ok(b - b'0'),
+                    _ => bail!("Invalid tree height {:?}", b as char),
+                })
+                .collect::<anyhow::Result<Vec<u8>>>()
+        })
+        .flatten_ok()
+        .collect::<anyhow::Result<Vec<u8>>>()?;
+        let w = w.unwrap_or(0);
+        let h = data.len() / w.max(1);
+        Ok(Grid { data, w, h })
     }
 
-    fn is_visible(&self, x: usize, y: usize) -> bool {
-        let cur_x = x as isize;
-        let cur_y = y as isize;
-        let cur = self.get(cur_x, cur_y).unwrap();
-        'outer: for (dx, dy) in DELTAS {
-            let mut x = cur_x + dx;
-            let mut y = cur_y + dy;
-            while let Some(val) = self.get(x, y) {
-                if val >= cur {
-                    continue 'outer;
+    /// Visibility and scenic score of every tree, indexed like `data`.
+    ///
+    /// Each row and column is swept in both directions in O(w·h) overall,
+    /// keeping a stack of the trees that can still block the view,
+    /// non-increasing in height from the bottom.
+    fn views(&self) -> (Vec<bool>, Vec<usize>) {
+        let mut visible = vec![false; self.data.len()];
+        let mut scores = vec![1; self.data.len()];
+        let rows = (0..self.h).map(|y| (y * self.w, 1, self.w));
+        let columns = (0..self.w).map(|x| (x, self.w, self.h));
+        let mut stack: Vec<(usize, u8)> = Vec::new();
+        for (start, step, len) in rows.chain(columns) {
+            for reversed in [false, true] {
+                stack.clear();
+                for k in 0..len {
+                    let ndx = start + step * if reversed { len - 1 - k } else { k };
+                    let height = self.data[ndx];
+                    while stack.last().is_some_and(|&(_, top)| top < height) {
+                        stack.pop();
+                    }
+                    match stack.last() {
+                        Some(&(blocker, _)) => scores[ndx] *= k - blocker,
+                        None => {
+                            visible[ndx] = true;
+                            scores[ndx] *= k;
+                        }
+                    }
+                    stack.push((k, height));
                 }
-                x += dx;
-                y += dy;
             }
-            return true;
-        }
-        false
-    }
-
-    fn scenic_score(&self, x: usize, y: usize) -> usize {
-        let cur_x = x as isize;
-        let cur_y = y as isize;
-        let cur = self.get(cur_x, cur_y).unwrap();
-        let mut total_score = 1;
-        for (dx, dy) in DELTAS {
-            let mut x = cur_x + dx;
-            let mut y = cur_y + dy;
-            let mut dir_score = 0;
-            while let Some(val) = self.get(x, y) {
-                dir_score += 1;
-                if val >= cur {
-                    break;
-                }
-                x += dx;
-                y += dy;
-            }
-            total_score *= dir_score;
         }
-        total_score
+        (visible, scores)
     }
 
     fn iter(&self) -> GridIter<'_> {
@@ -110,6 +112,137 @@ impl<'a> Iterator for GridIter<'a> {
     }
 }
 
+/// What a [`Heatmap`] colours each tree by
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Layer {
+    /// Green when visible from outside the forest, grey otherwise
+    Visibility,
+    /// From black through red to yellow as the scenic score grows
+    Scenic,
+}
+
+impl FromStr for Layer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "visibility" => Layer::Visibility,
+            "scenic" => Layer::Scenic,
+            _ => bail!("Unknown layer {s:?}, expected visibility or scenic"),
+        })
+    }
+}
+
+/// Visibility and scenic score of every tree, for inspecting a forest as an
+/// image. The tree with the best scenic score is drawn in white.
+pub struct Heatmap {
+    grid: Grid,
+    visible: Vec<bool>,
+    scores: Vec<usize>,
+    best: Option<(usize, usize, usize)>,
+}
+
+impl Heatmap {
+    pub fn new(input: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let grid = Grid::parse(input)?;
+        let (visible, scores) = grid.views();
+        let best = grid
+            .iter()
+            .zip(&scores)
+            .fold(None, |best, ((x, y), &score)| match best {
+                Some((_, _, best_score)) if best_score >= score => best,
+                _ => Some((x, y, score)),
+            });
+        Ok(Heatmap {
+            grid,
+            visible,
+            scores,
+            best,
+        })
+    }
+
+    /// `(x, y, score)` of the most scenic tree, the first in reading order on ties
+    pub fn best_tree(&self) -> Option<(usize, usize, usize)> {
+        self.best
+    }
+
+    fn colour(&self, x: usize, y: usize, layer: Layer) -> [u8; 3] {
+        let ndx = y * self.grid.w + x;
+        let best = self.best;
+        if matches!(best, Some((bx, by, _)) if (bx, by) == (x, y)) {
+            return [255, 255, 255];
+        }
+        match layer {
+            Layer::Visibility if self.visible[ndx] => [40, 170, 60],
+            Layer::Visibility => [60, 60, 60],
+            Layer::Scenic => {
+                let max = best.map_or(0, |(_, _, score)| score).max(1);
+                // square root spreads out the many low scores
+                let t = (self.scores[ndx] as f64 / max as f64).sqrt();
+                let red = (2.0 * t).min(1.0);
+                let green = (2.0 * t - 1.0).max(0.0);
+                [(255.0 * red) as u8, (255.0 * green) as u8, 0]
+            }
+        }
+    }
+
+    /// Binary PPM image with one pixel per tree
+    pub fn to_ppm(&self, layer: Layer) -> Vec<u8> {
+        let mut out = format!("P6\n{} {}\n255\n", self.grid.w, self.grid.h).into_bytes();
+        for (x, y) in self.grid.iter() {
+            out.extend(self.colour(x, y, layer));
+        }
+        out
+    }
+
+    /// Tree heights on 24-bit ANSI colour backgrounds, followed by the
+    /// position of the best tree
+    pub fn to_ansi(&self, layer: Layer) -> String {
+        let mut out = String::new();
+        for (x, y) in self.grid.iter() {
+            let [r, g, b] = self.colour(x, y, layer);
+            let height = self.grid.data[y * self.grid.w + x];
+            // dark digits on light backgrounds and the other way around
+            let fg = if r as u32 + g as u32 + b as u32 > 300 {
+                30
+            } else {
+                97
+            };
+            out += &format!("\x1b[48;2;{r};{g};{b};{fg}m{height}");
+            if x + 1 == self.grid.w {
+                out += "\x1b[0m\n";
+            }
+        }
+        if let Some((x, y, score)) = self.best {
+            out += &format!("Best tree at x={x}, y={y} with a scenic score of {score}\n");
+        }
+        out
+    }
+}
+
+/// Exploration commands, see `main`. `LAYER` is `visibility` or `scenic`.
+/// - `ansi LAYER`: the forest on coloured terminal backgrounds
+/// - `ppm LAYER FILE`: write the forest as a PPM image to `FILE`
+/// - `best`: the most scenic tree
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    let heatmap = Heatmap::new(input)?;
+    let mut out = String::new();
+    match args {
+        ["ansi", layer] => out = heatmap.to_ansi(layer.parse()?),
+        ["ppm", layer, file] => {
+            std::fs::write(file, heatmap.to_ppm(layer.parse()?))?;
+            let (w, h) = (heatmap.grid.w, heatmap.grid.h);
+            writeln!(out, "Wrote {w}x{h} image to {file}")?;
+        }
+        ["best"] => match heatmap.best_tree() {
+            Some((x, y, score)) => writeln!(out, "x={x}, y={y}, scenic score {score}")?,
+            None => writeln!(out, "No trees")?,
+        },
+        _ => bail!("Expected `ansi LAYER`, `ppm LAYER FILE` or `best`"),
+    }
+    Ok(out)
+}
+
 pub struct Part1;
 
 impl Part for Part1 {
@@ -118,10 +251,8 @@ impl Part for Part1 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(21));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let grid = Grid::parse(input);
-        Ok(Num(
-            grid.iter().filter(|&(x, y)| grid.is_visible(x, y)).count() as u64,
-        ))
+        let (visible, _) = Grid::parse(input)?.views();
+        Ok(Num(visible.into_iter().filter(|&v| v).count() as u64))
     }
 }
 
@@ -133,11 +264,200 @@ impl Part for Part2 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(8));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let grid = Grid::parse(input);
-        Ok(Num(grid
-            .iter()
-            .map(|(x, y)| grid.scenic_score(x, y))
-            .max()
-            .unwrap_or(0) as u64))
+        let (_, scores) = Grid::parse(input)?.views();
+        Ok(Num(scores.into_iter().max().unwrap_or(0) as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELTAS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+    // Reference implementations of `views`, walking every ray
+    impl Grid {
+        fn get(&self, x: isize, y: isize) -> Option<u8> {
+            if x < 0 || y < 0 || x as usize >= self.w || y as usize >= self.h {
+                return None;
+            }
+            self.data.get(y as usize * self.w + x as usize).copied()
+        }
+
+        fn is_visible(&self, x: usize, y: usize) -> bool {
+            let cur_x = x as isize;
+            let cur_y = y as isize;
+            let cur = self.get(cur_x, cur_y).unwrap();
+            'outer: for (dx, dy) in DELTAS {
+                let mut x = cur_x + dx;
+                let mut y = cur_y + dy;
+                while let Some(val) = self.get(x, y) {
+                    if val >= cur {
+                        continue 'outer;
+                    }
+                    x += dx;
+                    y += dy;
+                }
+                return true;
+            }
+            false
+        }
+
+        fn scenic_score(&self, x: usize, y: usize) -> usize {
+            let cur_x = x as isize;
+            let cur_y = y as isize;
+            let cur = self.get(cur_x, cur_y).unwrap();
+            let mut total_score = 1;
+            for (dx, dy) in DELTAS {
+                let mut x = cur_x + dx;
+                let mut y = cur_y + dy;
+                let mut dir_score = 0;
+                while let Some(val) = self.get(x, y) {
+                    dir_score += 1;
+                    if val >= cur {
+                        break;
+                    }
+                    x += dx;
+                    y += dy;
+                }
+                total_score *= dir_score;
+            }
+            total_score
+        }
+    }
+
+    /// Grids of random size and height range, from a fixed seed
+    fn random_grids(count: usize) -> impl Iterator<Item = Vec<String>> {
+        let mut seed = 0x2022_0008_u64;
+        let mut next = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        (0..count).map(move |_| {
+            let (w, h, heights) = (1 + next() % 12, 1 + next() % 12, 1 + next() % 10);
+            (0..h)
+                .map(|_| {
+                    (0..w)
+                        .map(|_| char::from(b'0' + (next() % heights) as u8))
+                        .collect()
+                })
+                .collect()
+        })
+    }
+
+    fn example() -> Heatmap {
+        Heatmap::new(Day8::EXAMPLE.trim().lines().map(String::from)).unwrap()
+    }
+
+    #[test]
+    fn ppm_export() {
+        let heatmap = Heatmap::new(["1234", "5678", "9012"].into_iter().map(String::from)).unwrap();
+        for layer in [Layer::Visibility, Layer::Scenic] {
+            let ppm = heatmap.to_ppm(layer);
+            let header = "P6\n4 3\n255\n".as_bytes();
+            assert_eq!(&ppm[..header.len()], header);
+            assert_eq!(ppm.len() - header.len(), 3 * 4 * 3);
+        }
+    }
+
+    /// RGB of the tree at `(x, y)` in a PPM export of the example
+    fn pixel(heatmap: &Heatmap, layer: Layer, x: usize, y: usize) -> [u8; 3] {
+        let ppm = heatmap.to_ppm(layer);
+        let start = "P6\n5 5\n255\n".len() + 3 * (y * 5 + x);
+        ppm[start..start + 3].try_into().unwrap()
+    }
+
+    #[test]
+    fn best_tree_is_white() {
+        let heatmap = example();
+        assert_eq!(heatmap.best_tree(), Some((2, 3, 8)));
+        for layer in [Layer::Visibility, Layer::Scenic] {
+            assert_eq!(pixel(&heatmap, layer, 2, 3), [255, 255, 255]);
+        }
+        // the corners see nothing but are visible
+        assert_eq!(pixel(&heatmap, Layer::Scenic, 0, 0), [0, 0, 0]);
+        assert_eq!(pixel(&heatmap, Layer::Visibility, 0, 0), [40, 170, 60]);
+        // the 1 in the middle of the forest is hidden
+        assert_eq!(pixel(&heatmap, Layer::Visibility, 3, 3), [60, 60, 60]);
+    }
+
+    #[test]
+    fn ansi_export() {
+        let ansi = example().to_ansi(Layer::Visibility);
+        let lines: Vec<_> = ansi.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[..5].iter().all(|line| line.ends_with("\x1b[0m")));
+        assert!(lines[0].starts_with("\x1b[48;2;40;170;60;97m3"));
+        assert_eq!(lines[5], "Best tree at x=2, y=3 with a scenic score of 8");
+    }
+
+    #[test]
+    fn explore_commands() {
+        let input = || Day8::EXAMPLE.trim().lines().map(String::from);
+        assert_eq!(
+            explore(&["best"], input()).unwrap(),
+            "x=2, y=3, scenic score 8\n"
+        );
+        assert!(explore(&["ansi", "scenic"], input()).is_ok());
+        assert!(explore(&["ansi", "height"], input()).is_err());
+        assert!(explore(&["ppm", "scenic"], input()).is_err());
+    }
+
+    #[test]
+    fn empty_and_malformed_forests() {
+        let lines = |lines: &[&str]| {
+            lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            explore(&["best"], std::iter::empty()).unwrap(),
+            "No trees\n"
+        );
+        assert_eq!(
+            explore(&["ansi", "scenic"], std::iter::empty()).unwrap(),
+            ""
+        );
+        let Num(visible) = Part1::run(std::iter::empty()).unwrap() else {
+            panic!("expected a number");
+        };
+        assert_eq!(visible, 0);
+        let err = |rows: &[&str]| {
+            let err = Grid::parse(lines(rows).into_iter()).err().unwrap();
+            format!("{err:#}")
+        };
+        assert_eq!(
+            err(&["123", "45", "678"]),
+            "line 2: \"45\": Expected 3 trees, found 2"
+        );
+        assert_eq!(err(&["", "1"]), "line 1: \"\": Empty row of trees");
+        assert_eq!(
+            err(&["12", "3x"]),
+            "line 2: \"3x\": Invalid tree height 'x'"
+        );
+    }
+
+    #[test]
+    fn views_match_reference() {
+        for lines in random_grids(2000) {
+            let grid = Grid::parse(lines.iter().cloned()).unwrap();
+            let (visible, scores) = grid.views();
+            for (x, y) in grid.iter() {
+                let ndx = y * grid.w + x;
+                assert_eq!(
+                    visible[ndx],
+                    grid.is_visible(x, y),
+                    "({x}, {y}) in {lines:?}"
+                );
+                assert_eq!(
+                    scores[ndx],
+                    grid.scenic_score(x, y),
+                    "({x}, {y}) in {lines:?}"
+                );
+            }
+        }
     }
 }
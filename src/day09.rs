@@ -1,9 +1,16 @@
 use std::{
     collections::HashSet,
-    ops::{AddAssign, Sub},
+    fmt::Write,
+    iter,
+    ops::{Add, AddAssign, Sub},
 };
 
-use aoc_framework::{anyhow::bail, *};
+use aoc_framework::{
+    anyhow::{anyhow, bail},
+    *,
+};
+
+use crate::helpers::{parse_lines, TileSet};
 
 pub struct Day9;
 
@@ -19,9 +26,19 @@ U 20
 ");
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Hash)]
-struct Point {
-    x: isize,
-    y: isize,
+pub struct Point {
+    pub x: isize,
+    pub y: isize,
+}
+
+impl Add for Point {
+    type Output = Point;
+    fn add(self, rhs: Self) -> Self::Output {
+        Point {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+        }
+    }
 }
 
 impl Sub for Point {
@@ -57,53 +74,220 @@ impl Point {
     }
 }
 
-struct Rope(Vec<Point>);
+/// Knots of a rope, stored as the head and the links from each knot to the
+/// next one, run-length encoded from the head.
+///
+/// A step is passed down the rope one run of equal links at a time, since
+/// every knot of a run then follows the previous one by the same move. A rope
+/// with few bends costs O(bends) per step however many knots it has.
+pub struct Rope {
+    head: Point,
+    tail: Point,
+    /// `(link, count)` for runs of knots that are `link` behind the previous one
+    links: Vec<(Point, usize)>,
+}
 
 impl Rope {
+    pub fn new(knots: usize) -> anyhow::Result<Self> {
+        if knots == 0 {
+            bail!("A rope needs at least one knot");
+        }
+        let links = match knots {
+            1 => Vec::new(),
+            _ => vec![(Point::default(), knots - 1)],
+        };
+        Ok(Rope {
+            head: Point::default(),
+            tail: Point::default(),
+            links,
+        })
+    }
+
     fn step(&mut self, delta: Point) -> Point {
-        self.0[0] += delta;
-        let mut prev = self.0[0];
-        for knot in self.0.iter_mut().skip(1) {
-            let delta = prev - *knot;
-            match delta.abs() {
-                Point { x: 0 | 1, y: 0 | 1 } => break,
-                _ => {
-                    *knot += delta.signum();
-                    prev = *knot;
+        self.head += delta;
+        // how the previous knot moved
+        let mut moved = delta;
+        let mut run = 0;
+        while let Some(&(link, _)) = self.links.get(run) {
+            let pulled = link + moved;
+            if pulled.abs().x <= 1 && pulled.abs().y <= 1 {
+                // still touching, the rest of the rope stays put
+                self.set_first_link(run, pulled);
+                self.merge_runs(run + 2);
+                return self.tail;
+            }
+            let step = pulled.signum();
+            if step == moved {
+                // the whole run moves along without changing shape
+                run += 1;
+            } else {
+                run = self.set_first_link(run, pulled - step);
+                moved = step;
+            }
+        }
+        self.merge_runs(self.links.len());
+        self.tail += moved;
+        self.tail
+    }
+
+    /// Changes the link of the first knot of `run`, splitting it off the run
+    /// if needed. Returns the run holding the next knot.
+    fn set_first_link(&mut self, run: usize, link: Point) -> usize {
+        match &mut self.links[run] {
+            (first, 1) => *first = link,
+            (_, count) => {
+                *count -= 1;
+                self.links.insert(run, (link, 1));
+            }
+        }
+        run + 1
+    }
+
+    /// Merges neighbouring runs with equal links among the first `end` runs
+    fn merge_runs(&mut self, end: usize) {
+        let end = end.min(self.links.len());
+        let mut kept = 0;
+        for run in 1..end {
+            if self.links[run].0 == self.links[kept].0 {
+                self.links[kept].1 += self.links[run].1;
+            } else {
+                kept += 1;
+                self.links[kept] = self.links[run];
+            }
+        }
+        if end > 0 {
+            self.links.drain(kept + 1..end);
+        }
+    }
+
+    pub fn knots(&self) -> Vec<Point> {
+        let links = self
+            .links
+            .iter()
+            .flat_map(|&(link, count)| iter::repeat_n(link, count));
+        let behind = links.scan(self.head, |knot, link| {
+            *knot = *knot - link;
+            Some(*knot)
+        });
+        iter::once(self.head).chain(behind).collect()
+    }
+
+    /// Runs a motion script, recording where every knot is after each step
+    pub fn trace(&mut self, moves: impl IntoIterator<Item = (Point, usize)>) -> Trajectories {
+        let mut knots: Vec<Vec<Point>> = self.knots().into_iter().map(|knot| vec![knot]).collect();
+        for (delta, dist) in moves {
+            for _ in 0..dist {
+                self.step(delta);
+                for (trajectory, knot) in knots.iter_mut().zip(self.knots()) {
+                    trajectory.push(knot);
                 }
             }
         }
-        self.0.last().copied().unwrap()
+        Trajectories { knots }
+    }
+}
+
+/// Positions of each knot of a rope over time, starting before the first move
+pub struct Trajectories {
+    knots: Vec<Vec<Point>>,
+}
+
+impl Trajectories {
+    pub fn knot(&self, knot: usize) -> Option<&[Point]> {
+        self.knots.get(knot).map(Vec::as_slice)
+    }
+
+    /// Map of the cells visited by a knot as in the puzzle text: `#` for
+    /// visited cells, `s` for the start and `.` elsewhere, up being north.
+    pub fn render(&self, knot: usize) -> Option<String> {
+        let trajectory = self.knot(knot)?;
+        let visited: HashSet<Point> = trajectory.iter().copied().collect();
+        let (min_x, max_x) = visited.iter().map(|p| p.x).minmax().into_option()?;
+        let (min_y, max_y) = visited.iter().map(|p| p.y).minmax().into_option()?;
+        let mut out = String::new();
+        for y in (min_y..=max_y).rev() {
+            out.extend((min_x..=max_x).map(|x| {
+                let p = Point { x, y };
+                if p == trajectory[0] {
+                    's'
+                } else if visited.contains(&p) {
+                    '#'
+                } else {
+                    '.'
+                }
+            }));
+            out.push('\n');
+        }
+        Some(out)
     }
 }
 
-fn parse_line(line: &str) -> anyhow::Result<(Point, usize)> {
-    let (dir, dist) = line.split_once(' ').unwrap();
-    let (x, y) = match dir.as_bytes()[0] {
-        b'L' => (-1, 0),
-        b'U' => (0, 1),
-        b'R' => (1, 0),
-        b'D' => (0, -1),
-        _ => bail!("Invalid direction {dir}"),
+/// Parses a motion such as `R 4`, allowing diagonal directions like `UL`
+pub fn parse_line(line: &str) -> anyhow::Result<(Point, usize)> {
+    let (dir, dist) = line
+        .split_once(' ')
+        .ok_or_else(|| anyhow!("Expected a direction and a distance"))?;
+    let mut delta = Point::default();
+    for c in dir.bytes() {
+        delta += match c {
+            b'L' => Point { x: -1, y: 0 },
+            b'U' => Point { x: 0, y: 1 },
+            b'R' => Point { x: 1, y: 0 },
+            b'D' => Point { x: 0, y: -1 },
+            _ => bail!("Invalid direction {dir}"),
+        };
+    }
+    if delta == Point::default() || delta.abs().x > 1 || delta.abs().y > 1 {
+        bail!("Invalid direction {dir}");
+    }
+    Ok((delta, dist.parse()?))
+}
+
+/// Exploration commands, see `main`:
+/// - `render KNOTS [KNOT]`: map of the cells visited by a knot of a rope with
+///   `KNOTS` knots, the tail by default
+/// - `final KNOTS`: where every knot ends up
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    let moves = parse_lines(input, parse_line).collect::<anyhow::Result<Vec<_>>>()?;
+    let (knots, knot) = match args {
+        ["render", knots] | ["final", knots] => (knots.parse::<usize>()?, None),
+        ["render", knots, knot] => (knots.parse()?, Some(knot.parse::<usize>()?)),
+        _ => bail!("Expected `render KNOTS [KNOT]` or `final KNOTS`"),
     };
-    Ok((Point { x, y }, dist.parse()?))
+    let mut rope = Rope::new(knots)?;
+    let trajectories = rope.trace(moves);
+    if args[0] == "render" {
+        let knot = knot.unwrap_or(knots - 1);
+        return trajectories
+            .render(knot)
+            .ok_or_else(|| anyhow!("Knot {knot} is not on a rope of {knots} knots"));
+    }
+    let mut out = String::new();
+    for (i, knot) in rope.knots().iter().enumerate() {
+        writeln!(out, "{i}: {}, {}", knot.x, knot.y)?;
+    }
+    Ok(out)
 }
 
+/// Number of distinct cells visited by the tail.
+///
+/// Steps cost O(bends in the rope) rather than O(knots), and memory follows
+/// the cells the tail visits rather than their bounding box.
 fn count_tail_positions(
     rope_len: usize,
     input: impl Iterator<Item = String>,
 ) -> anyhow::Result<Answer> {
-    let mut rope = Rope(vec![Point::default(); rope_len]);
-    let mut tail_positions = HashSet::new();
-    tail_positions.insert(Point::default());
-    for line in input {
-        let (delta, dist) = parse_line(&line)?;
+    let mut rope = Rope::new(rope_len)?;
+    let mut visited = TileSet::new();
+    visited.insert(0, 0);
+    for motion in parse_lines(input, parse_line) {
+        let (delta, dist) = motion?;
         for _ in 0..dist {
             let tail = rope.step(delta);
-            tail_positions.insert(tail);
+            visited.insert(tail.x, tail.y);
         }
     }
-    Ok(Num(tail_positions.len() as u64))
+    Ok(Num(visited.len() as u64))
 }
 
 pub struct Part1;
@@ -129,3 +313,149 @@ impl Part for Part2 {
         count_tail_positions(10, input)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL_EXAMPLE: &str = "R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2";
+
+    fn moves(input: &str) -> Vec<(Point, usize)> {
+        input
+            .trim()
+            .lines()
+            .map(|line| parse_line(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn diagonal_moves() {
+        assert_eq!(parse_line("UR 3").unwrap(), (Point { x: 1, y: 1 }, 3));
+        assert_eq!(parse_line("DL 1").unwrap(), (Point { x: -1, y: -1 }, 1));
+        for line in ["LR 3", "UU 1", "X 1", "R", "R x"] {
+            assert!(parse_line(line).is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn final_knots() {
+        let mut rope = Rope::new(10).unwrap();
+        let trajectories = rope.trace(moves(SMALL_EXAMPLE));
+        let expected = [(2, 2), (1, 2), (2, 2), (3, 2), (2, 2), (1, 1)]
+            .into_iter()
+            .chain([(0, 0); 4])
+            .map(|(x, y)| Point { x, y })
+            .collect::<Vec<_>>();
+        assert_eq!(rope.knots(), expected);
+        // the start plus one position per step
+        assert_eq!(trajectories.knot(9).unwrap().len(), 25);
+        assert!(trajectories.knot(10).is_none());
+    }
+
+    #[test]
+    fn render() {
+        let trajectories = Rope::new(2).unwrap().trace(moves(SMALL_EXAMPLE));
+        // the puzzle's map, trimmed to the visited cells
+        let expected = "..##.\n...##\n.####\n....#\ns###.\n";
+        assert_eq!(trajectories.render(1).unwrap(), expected);
+
+        let trajectories = Rope::new(10).unwrap().trace(moves(Day9::EXAMPLE));
+        let expected = [
+            "#.....................",
+            "#.............###.....",
+            "#............#...#....",
+            ".#..........#.....#...",
+            "..#..........#.....#..",
+            "...#........#.......#.",
+            "....#......s.........#",
+            ".....#..............#.",
+            "......#............#..",
+            ".......#..........#...",
+            "........#........#....",
+            ".........########.....",
+        ];
+        assert_eq!(trajectories.render(9).unwrap(), expected.join("\n") + "\n");
+    }
+
+    /// Every knot of a rope after each step, moving one knot at a time
+    fn naive_trace(knots: usize, moves: &[(Point, usize)]) -> Vec<Vec<Point>> {
+        let mut rope = vec![Point::default(); knots];
+        let mut states = vec![rope.clone()];
+        for &(delta, dist) in moves {
+            for _ in 0..dist {
+                rope[0] += delta;
+                for i in 1..knots {
+                    let pulled = rope[i - 1] - rope[i];
+                    if pulled.abs().x > 1 || pulled.abs().y > 1 {
+                        rope[i] += pulled.signum();
+                    }
+                }
+                states.push(rope.clone());
+            }
+        }
+        states
+    }
+
+    #[test]
+    fn runs_match_naive_rope() {
+        let mut seed = 0x2022_0009_u64;
+        let mut next = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        let directions = ["L", "U", "R", "D", "UL", "UR", "DL", "DR"];
+        for knots in [1, 2, 3, 10, 50] {
+            for _ in 0..20 {
+                let moves: Vec<_> = (0..50)
+                    .map(|_| {
+                        let line = format!("{} {}", directions[next() % 8], 1 + next() % 30);
+                        parse_line(&line).unwrap()
+                    })
+                    .collect();
+                let trajectories = Rope::new(knots).unwrap().trace(moves.iter().copied());
+                for (step, state) in naive_trace(knots, &moves).iter().enumerate() {
+                    for (knot, &expected) in state.iter().enumerate() {
+                        assert_eq!(trajectories.knot(knot).unwrap()[step], expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn long_motions_stay_sparse() {
+        // the bounding box of these has 10^12 cells
+        let input = ["R 1000000", "U 1000000"].into_iter().map(String::from);
+        let Num(count) = count_tail_positions(10, input).unwrap() else {
+            panic!("expected a number");
+        };
+        assert_eq!(count, 1999983);
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release day09 -- --ignored --nocapture`"]
+    fn spiral_benchmark() {
+        // square spiral of 2000 motions growing from 1000 to 6000 cells
+        let spiral: Vec<String> = (0..2000)
+            .map(|i| format!("{} {}", ["R", "U", "L", "D"][i % 4], 1000 + 5 * i / 2))
+            .collect();
+        for knots in [2, 10, 1000, 5000] {
+            let start = std::time::Instant::now();
+            let count = count_tail_positions(knots, spiral.iter().cloned()).unwrap();
+            println!("{knots:>5} knots: {count:?} in {:?}", start.elapsed());
+        }
+    }
+
+    #[test]
+    fn explore_commands() {
+        let input = || SMALL_EXAMPLE.lines().map(String::from);
+        let out = explore(&["render", "2"], input()).unwrap();
+        assert_eq!(out, explore(&["render", "2", "1"], input()).unwrap());
+        assert!(explore(&["render", "2", "2"], input()).is_err());
+        assert!(explore(&["render", "0"], input()).is_err());
+        let out = explore(&["final", "2"], input()).unwrap();
+        assert_eq!(out, "0: 2, 2\n1: 1, 2\n");
+    }
+}
@@ -1,4 +1,17 @@
-use aoc_framework::*;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Write},
+    iter,
+    ops::{Index, IndexMut},
+    str::FromStr,
+};
+
+use aoc_framework::{
+    anyhow::{anyhow, bail},
+    *,
+};
+
+use crate::helpers::parse_lines;
 
 pub struct Day10;
 
@@ -151,33 +164,524 @@ noop
 noop
 ");
 
-struct Proc {
-    cycle: i64,
-    x: i64,
-    result: i64,
-    buffer: Vec<bool>,
+/// Registers of the CPU
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Register {
+    X,
+}
+
+impl Register {
+    pub const ALL: [Register; 1] = [Register::X];
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::X => f.write_str("X"),
+        }
+    }
+}
+
+impl FromStr for Register {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "X" => Ok(Register::X),
+            _ => bail!("Unknown register {s:?}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Registers([i64; Register::ALL.len()]);
+
+impl Default for Registers {
+    fn default() -> Self {
+        Registers([1])
+    }
+}
+
+impl Index<Register> for Registers {
+    type Output = i64;
+    fn index(&self, reg: Register) -> &i64 {
+        &self.0[reg as usize]
+    }
+}
+
+impl IndexMut<Register> for Registers {
+    fn index_mut(&mut self, reg: Register) -> &mut i64 {
+        &mut self.0[reg as usize]
+    }
+}
+
+impl Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut regs = Register::ALL
+            .iter()
+            .map(|&reg| format!("{reg}={}", self[reg]));
+        f.write_str(&regs.join(" "))
+    }
+}
+
+/// Decoded instruction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Instruction {
+    Noop,
+    Addx(i64),
+}
+
+impl Instruction {
+    /// Number of cycles the instruction takes to complete
+    pub fn cycles(self) -> u64 {
+        match self {
+            Instruction::Noop => 1,
+            Instruction::Addx(_) => 2,
+        }
+    }
+
+    fn execute(self, regs: &mut Registers) {
+        match self {
+            Instruction::Noop => {}
+            Instruction::Addx(val) => regs[Register::X] += val,
+        }
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut words = s.split_whitespace();
+        let ins = match (words.next(), words.next()) {
+            (Some("noop"), None) => Instruction::Noop,
+            (Some("addx"), Some(val)) => Instruction::Addx(val.parse()?),
+            _ => bail!("Unknown instruction"),
+        };
+        if words.next().is_some() {
+            bail!("Too many operands");
+        }
+        Ok(ins)
+    }
+}
+
+/// Prints the instruction in the syntax it is parsed from
+impl Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Addx(val) => write!(f, "addx {val}"),
+        }
+    }
+}
+
+fn parse_program(input: impl Iterator<Item = String>) -> anyhow::Result<Vec<Instruction>> {
+    parse_lines(input, str::parse).collect()
+}
+
+/// Listing of a program with the address and first cycle of each instruction
+pub fn disassemble(program: &[Instruction]) -> String {
+    let mut cycle = 1;
+    let mut out = String::new();
+    for (addr, ins) in program.iter().enumerate() {
+        out += &format!("{addr:>5}  cycle {cycle:>5}  {ins}\n");
+        cycle += ins.cycles();
+    }
+    out
+}
+
+/// Sees every cycle of the CPU, with the registers as they are during it
+pub trait Observer {
+    fn cycle(&mut self, cycle: u64, regs: &Registers);
+}
+
+impl<F: FnMut(u64, &Registers)> Observer for F {
+    fn cycle(&mut self, cycle: u64, regs: &Registers) {
+        self(cycle, regs)
+    }
 }
 
-impl Proc {
-    fn tick(&mut self) {
-        if self.cycle == 20 || (self.cycle > 20 && (self.cycle - 20) % 40 == 0) {
-            self.result += self.x * self.cycle;
+pub struct Cpu {
+    program: Vec<Instruction>,
+    regs: Registers,
+    pc: usize,
+    // cycles completed so far, and those spent on the current instruction
+    cycle: u64,
+    busy: u64,
+}
+
+impl Cpu {
+    pub fn new(program: Vec<Instruction>) -> Self {
+        Cpu {
+            program,
+            regs: Registers::default(),
+            pc: 0,
+            cycle: 0,
+            busy: 0,
         }
+    }
+
+    /// Runs a single cycle, returning `false` once the program has ended
+    pub fn step(&mut self, observer: &mut impl Observer) -> bool {
+        let Some(&ins) = self.program.get(self.pc) else {
+            return false;
+        };
         self.cycle += 1;
+        observer.cycle(self.cycle, &self.regs);
+        self.busy += 1;
+        if self.busy == ins.cycles() {
+            ins.execute(&mut self.regs);
+            self.pc += 1;
+            self.busy = 0;
+        }
+        true
+    }
+
+    pub fn run(&mut self, observer: &mut impl Observer) {
+        while self.step(observer) {}
+    }
+}
+
+impl Cpu {
+    pub fn registers(&self) -> &Registers {
+        &self.regs
+    }
+
+    /// Number of cycles completed
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// Address of the instruction being executed
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn program(&self) -> &[Instruction] {
+        &self.program
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Breakpoint {
+    /// Stops before the given cycle runs
+    Cycle(u64),
+    /// Stops before any cycle during which the register holds the value
+    Register(Register, i64),
+}
+
+/// Parses `cycle=N` or `REGISTER=VALUE`
+impl FromStr for Breakpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (what, val) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected `cycle=N` or `REGISTER=VALUE`, got {s:?}"))?;
+        Ok(match what {
+            "cycle" => Breakpoint::Cycle(val.parse()?),
+            reg => Breakpoint::Register(reg.parse()?, val.parse()?),
+        })
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Cycle(cycle) => write!(f, "cycle={cycle}"),
+            Breakpoint::Register(reg, val) => write!(f, "{reg}={val}"),
+        }
+    }
+}
+
+/// Step debugger around a [`Cpu`]
+pub struct Debugger {
+    cpu: Cpu,
+    breakpoints: Vec<Breakpoint>,
+    // breakpoints that already stopped the CPU in its current state
+    fired: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    pub fn new(cpu: Cpu) -> Self {
+        Debugger {
+            cpu,
+            breakpoints: Vec::new(),
+            fired: Vec::new(),
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.retain(|&bp| bp != breakpoint);
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    fn hit(&self) -> Option<Breakpoint> {
+        if self.cpu.pc >= self.cpu.program.len() {
+            return None;
+        }
+        let next = self.cpu.cycle + 1;
+        self.breakpoints.iter().copied().find(|&bp| {
+            !self.fired.contains(&bp)
+                && match bp {
+                    Breakpoint::Cycle(cycle) => cycle == next,
+                    Breakpoint::Register(reg, val) => self.cpu.regs[reg] == val,
+                }
+        })
     }
 
-    fn update_buffer(&mut self) {
-        let x = (self.cycle - 1) % 40;
-        let y = (self.cycle - 1) / 40;
-        if y > 5 {
+    /// Runs until the first breakpoint hit, which may be before the next
+    /// cycle, or until the end of the program. Returns the breakpoint that
+    /// stopped it. Each breakpoint stops the CPU at most once in a given state.
+    pub fn resume(&mut self, observer: &mut impl Observer) -> Option<Breakpoint> {
+        loop {
+            if let Some(bp) = self.hit() {
+                self.fired.push(bp);
+                return Some(bp);
+            }
+            if !self.cpu.step(observer) {
+                return None;
+            }
+            self.fired.clear();
+        }
+    }
+
+    /// State of the CPU before its next cycle
+    pub fn dump(&self) -> String {
+        let cpu = self.cpu();
+        let next = match cpu.program().get(cpu.pc()) {
+            Some(ins) => format!("{ins} ({}/{})", cpu.busy + 1, ins.cycles()),
+            None => "halted".to_string(),
+        };
+        let (cycle, pc, regs) = (cpu.cycle() + 1, cpu.pc(), cpu.registers());
+        format!("cycle {cycle} pc {pc} {next}: {regs}")
+    }
+}
+
+/// Cycles at which the signal strength is sampled: `first`, then every `every`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Schedule {
+    pub first: u64,
+    pub every: u64,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            first: 20,
+            every: 40,
+        }
+    }
+}
+
+impl Schedule {
+    pub fn contains(&self, cycle: u64) -> bool {
+        match cycle.checked_sub(self.first) {
+            Some(since) if self.every == 0 => since == 0,
+            Some(since) => since.is_multiple_of(self.every),
+            None => false,
+        }
+    }
+}
+
+/// Sums `cycle * X` over the cycles of a [`Schedule`]
+pub struct SignalStrength {
+    schedule: Schedule,
+    total: i64,
+}
+
+impl SignalStrength {
+    pub fn new(schedule: Schedule) -> Self {
+        SignalStrength { schedule, total: 0 }
+    }
+
+    pub fn total(&self) -> i64 {
+        self.total
+    }
+}
+
+impl Observer for SignalStrength {
+    fn cycle(&mut self, cycle: u64, regs: &Registers) {
+        if self.schedule.contains(cycle) {
+            self.total += regs[Register::X] * cycle as i64;
+        }
+    }
+}
+
+/// Resolution of a CRT and width of the sprite it draws
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Screen {
+    pub width: usize,
+    pub height: usize,
+    pub sprite_width: usize,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Screen {
+            width: 40,
+            height: 6,
+            sprite_width: 3,
+        }
+    }
+}
+
+/// Screen drawing one pixel per cycle, lit when the sprite centered on `X`
+/// covers it. Pixels drawn after the last one of the screen are dropped.
+pub struct Crt {
+    screen: Screen,
+    buffer: Vec<bool>,
+}
+
+impl Crt {
+    pub fn new(screen: Screen) -> Self {
+        Crt {
+            screen,
+            buffer: vec![false; screen.width * screen.height],
+        }
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &[bool]> {
+        self.buffer.chunks(self.screen.width.max(1))
+    }
+
+    /// Each row on a new line, `#` for lit pixels and spaces otherwise
+    pub fn to_text(&self) -> String {
+        self.rows()
+            .flat_map(|row| {
+                iter::once('\n').chain(row.iter().map(|&lit| if lit { '#' } else { ' ' }))
+            })
+            .collect()
+    }
+
+    /// Plain PBM image, lit pixels being black
+    pub fn to_pbm(&self) -> String {
+        let mut out = format!("P1\n{} {}\n", self.screen.width, self.screen.height);
+        for row in self.rows() {
+            out.extend(Itertools::intersperse(
+                row.iter().map(|&lit| if lit { '1' } else { '0' }),
+                ' ',
+            ));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Rows of two-character blocks with white or black ANSI backgrounds
+    pub fn to_ansi(&self) -> String {
+        let mut out = String::new();
+        for row in self.rows() {
+            for &lit in row {
+                out += if lit { "\x1b[47m  " } else { "\x1b[40m  " };
+            }
+            out += "\x1b[0m\n";
+        }
+        out
+    }
+}
+
+impl Observer for Crt {
+    fn cycle(&mut self, cycle: u64, regs: &Registers) {
+        let pixel = cycle as usize - 1;
+        if pixel >= self.buffer.len() {
             return;
         }
-        if (x - self.x).abs() < 2 {
-            self.buffer[self.cycle as usize - 1] = true;
+        let x = (pixel % self.screen.width) as i64;
+        let left = regs[Register::X] - (self.screen.sprite_width as i64 - 1) / 2;
+        if (left..left + self.screen.sprite_width as i64).contains(&x) {
+            self.buffer[pixel] = true;
         }
     }
 }
 
+/// Splits `NAME=VALUE` options
+fn options<'a>(args: &[&'a str]) -> anyhow::Result<Vec<(&'a str, &'a str)>> {
+    args.iter()
+        .map(|arg| {
+            arg.split_once('=')
+                .ok_or_else(|| anyhow!("Expected NAME=VALUE, found {arg:?}"))
+        })
+        .collect()
+}
+
+/// Exploration commands, see `main`:
+/// - `disassemble`: the program with the first cycle of each instruction
+/// - `debug BREAKPOINT...`: CPU state at each breakpoint, `cycle=N` or `X=VALUE`.
+///   Each breakpoint stops the program once.
+/// - `signal [first=N] [every=N]`: sum of the signal strengths sampled at
+///   cycle `first`, then every `every` cycles (20 and 40 by default)
+/// - `crt text|pbm|ansi [width=N] [height=N] [sprite=N]`: the image drawn on a
+///   CRT of the given size and sprite width (40, 6 and 3 by default)
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    let program = parse_program(input)?;
+    let mut out = String::new();
+    match args {
+        ["disassemble"] => out = disassemble(&program),
+        ["debug", breakpoints @ ..] => {
+            let mut debugger = Debugger::new(Cpu::new(program));
+            for bp in breakpoints {
+                debugger.add_breakpoint(bp.parse()?);
+            }
+            while let Some(bp) = debugger.resume(&mut |_, _: &Registers| {}) {
+                writeln!(out, "{bp}: {}", debugger.dump())?;
+                debugger.remove_breakpoint(bp);
+            }
+            writeln!(out, "{}", debugger.dump())?;
+        }
+        ["signal", opts @ ..] => {
+            let mut schedule = Schedule::default();
+            for (name, value) in options(opts)? {
+                match name {
+                    "first" => schedule.first = value.parse()?,
+                    "every" => schedule.every = value.parse()?,
+                    _ => bail!("Unknown option {name:?}, expected first or every"),
+                }
+            }
+            let mut signal = SignalStrength::new(schedule);
+            Cpu::new(program).run(&mut signal);
+            writeln!(out, "{}", signal.total())?;
+        }
+        ["crt", format, opts @ ..] => {
+            let mut screen = Screen::default();
+            for (name, value) in options(opts)? {
+                match name {
+                    "width" => screen.width = value.parse()?,
+                    "height" => screen.height = value.parse()?,
+                    "sprite" => screen.sprite_width = value.parse()?,
+                    _ => bail!("Unknown option {name:?}, expected width, height or sprite"),
+                }
+            }
+            if screen.width.checked_mul(screen.height).is_none() {
+                bail!(
+                    "Screen of {}x{} pixels is too large",
+                    screen.width,
+                    screen.height
+                );
+            }
+            let mut crt = Crt::new(screen);
+            Cpu::new(program).run(&mut crt);
+            out = match *format {
+                "text" => crt.to_text(),
+                "pbm" => crt.to_pbm(),
+                "ansi" => crt.to_ansi(),
+                _ => bail!("Unknown format {format:?}, expected text, pbm or ansi"),
+            };
+        }
+        _ => bail!(
+            "Expected `disassemble`, `debug BREAKPOINT...`, `signal [OPTION...]` \
+             or `crt text|pbm|ansi [OPTION...]`"
+        ),
+    }
+    Ok(out)
+}
+
 pub struct Part1;
 
 impl Part for Part1 {
@@ -186,23 +690,9 @@ impl Part for Part1 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(13140));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let mut proc = Proc {
-            cycle: 1,
-            x: 1,
-            result: 0,
-            buffer: Vec::new(),
-        };
-        for line in input {
-            if let Some(arg) = line.strip_prefix("addx ") {
-                let val = arg.parse::<i64>()?;
-                proc.tick();
-                proc.tick();
-                proc.x += val;
-            } else {
-                proc.tick();
-            }
-        }
-        Ok(Num(proc.result as u64))
+        let mut signal = SignalStrength::new(Schedule::default());
+        Cpu::new(parse_program(input)?).run(&mut signal);
+        Ok(Num(signal.total() as u64))
     }
 }
 
@@ -223,36 +713,184 @@ impl Part for Part2 {
     ));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let mut proc = Proc {
-            cycle: 1,
-            x: 1,
-            result: 0,
-            buffer: vec![false; 40 * 6],
-        };
-        for line in input {
-            if let Some(arg) = line.strip_prefix("addx ") {
-                let val = arg.parse::<i64>()?;
-                proc.update_buffer();
-                proc.tick();
-                proc.update_buffer();
-                proc.tick();
-                proc.x += val;
-            } else {
-                proc.update_buffer();
-                proc.tick();
-            }
+        let mut crt = Crt::new(Screen::default());
+        Cpu::new(parse_program(input)?).run(&mut crt);
+        Ok(Str(Cow::Owned(crt.to_text())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(lines: &[&str]) -> Vec<Instruction> {
+        lines.iter().map(|line| line.parse().unwrap()).collect()
+    }
+
+    fn small_program() -> Vec<Instruction> {
+        program(&["noop", "addx 3", "addx -5", "noop"])
+    }
+
+    #[test]
+    fn disassemble_shows_start_cycles() {
+        let listing = disassemble(&small_program());
+        let expected = [
+            "    0  cycle     1  noop",
+            "    1  cycle     2  addx 3",
+            "    2  cycle     4  addx -5",
+            "    3  cycle     6  noop",
+        ];
+        assert_eq!(listing.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn cycle_breakpoint() {
+        let mut debugger = Debugger::new(Cpu::new(small_program()));
+        debugger.add_breakpoint(Breakpoint::Cycle(3));
+        let mut seen = Vec::new();
+        let stop = debugger.resume(&mut |cycle, _: &Registers| seen.push(cycle));
+        assert_eq!(stop, Some(Breakpoint::Cycle(3)));
+        // stopped before cycle 3, halfway through `addx 3`
+        assert_eq!(seen, [1, 2]);
+        assert_eq!(debugger.cpu().cycle(), 2);
+        assert_eq!(debugger.dump(), "cycle 3 pc 1 addx 3 (2/2): X=1");
+        assert_eq!(debugger.resume(&mut |_, _: &Registers| {}), None);
+        assert_eq!(debugger.cpu().cycle(), 6);
+    }
+
+    #[test]
+    fn breakpoints_before_the_first_cycle() {
+        let mut debugger = Debugger::new(Cpu::new(small_program()));
+        debugger.add_breakpoint(Breakpoint::Cycle(1));
+        debugger.add_breakpoint(Breakpoint::Register(Register::X, 1));
+        let mut log = |_, _: &Registers| {};
+        // both stop before anything runs, then the CPU moves on
+        assert_eq!(debugger.resume(&mut log), Some(Breakpoint::Cycle(1)));
+        assert_eq!(debugger.cpu().cycle(), 0);
+        assert_eq!(debugger.dump(), "cycle 1 pc 0 noop (1/1): X=1");
+        let x_is_1 = Breakpoint::Register(Register::X, 1);
+        assert_eq!(debugger.resume(&mut log), Some(x_is_1));
+        assert_eq!(debugger.cpu().cycle(), 0);
+        // X stays 1 until the end of cycle 3
+        assert_eq!(debugger.resume(&mut log), Some(x_is_1));
+        assert_eq!(debugger.cpu().cycle(), 1);
+        assert_eq!(debugger.resume(&mut log), Some(x_is_1));
+        assert_eq!(debugger.cpu().cycle(), 2);
+        assert_eq!(debugger.resume(&mut log), None);
+        // breakpoints do not fire once the program has ended
+        debugger.add_breakpoint(Breakpoint::Cycle(7));
+        assert_eq!(debugger.resume(&mut log), None);
+    }
+
+    #[test]
+    fn register_breakpoint() {
+        let bp = Breakpoint::Register(Register::X, 4);
+        let mut debugger = Debugger::new(Cpu::new(small_program()));
+        debugger.add_breakpoint(bp);
+        let mut log = |_, _: &Registers| {};
+        // X becomes 4 at the end of cycle 3, so cycles 4 and 5 run with it
+        assert_eq!(debugger.resume(&mut log), Some(bp));
+        assert_eq!(debugger.dump(), "cycle 4 pc 2 addx -5 (1/2): X=4");
+        assert_eq!(debugger.resume(&mut log), Some(bp));
+        assert_eq!(debugger.cpu().cycle(), 4);
+        debugger.remove_breakpoint(bp);
+        assert_eq!(debugger.resume(&mut log), None);
+        assert_eq!(debugger.cpu().registers()[Register::X], -1);
+        assert_eq!(debugger.dump(), "cycle 7 pc 4 halted: X=-1");
+    }
+
+    #[test]
+    fn parse_breakpoints() {
+        assert_eq!(
+            "cycle=20".parse::<Breakpoint>().unwrap(),
+            Breakpoint::Cycle(20)
+        );
+        let bp: Breakpoint = "X=-3".parse().unwrap();
+        assert_eq!(bp, Breakpoint::Register(Register::X, -3));
+        assert_eq!(bp.to_string(), "X=-3");
+        for s in ["cycle", "Y=1", "cycle=-1", "X=x"] {
+            assert!(s.parse::<Breakpoint>().is_err(), "{s}");
         }
-        let mut out = String::with_capacity(50);
-        for (i, pix) in proc.buffer.iter().enumerate() {
-            if i % 40 == 0 {
-                out.push('\n');
-            }
-            if *pix {
-                out.push('#');
-            } else {
-                out.push(' ');
-            }
+    }
+
+    #[test]
+    fn explore_commands() {
+        let input = || Day10::EXAMPLE.trim().lines().map(String::from);
+        let out = explore(&["debug", "cycle=1", "cycle=3"], input()).unwrap();
+        let expected = [
+            "cycle=1: cycle 1 pc 0 addx 15 (1/2): X=1",
+            "cycle=3: cycle 3 pc 1 addx -11 (1/2): X=16",
+            "cycle 241 pc 146 halted: X=17",
+        ];
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+        let out = explore(&["debug", "cycle=20", "X=21"], input()).unwrap();
+        let expected = [
+            "X=21: cycle 19 pc 9 noop (1/1): X=21",
+            "cycle=20: cycle 20 pc 10 addx -1 (1/2): X=21",
+            "cycle 241 pc 146 halted: X=17",
+        ];
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+
+        let pbm = explore(&["crt", "pbm"], input()).unwrap();
+        let mut lines = pbm.lines();
+        assert_eq!(lines.next(), Some("P1"));
+        assert_eq!(lines.next(), Some("40 6"));
+        assert_eq!(
+            lines.next(),
+            Some("1 1 0 0 1 1 0 0 1 1 0 0 1 1 0 0 1 1 0 0 1 1 0 0 1 1 0 0 1 1 0 0 1 1 0 0 1 1 0 0")
+        );
+        assert_eq!(lines.count(), 5);
+
+        let ansi = explore(&["crt", "ansi"], input()).unwrap();
+        assert_eq!(ansi.lines().count(), 6);
+        assert!(ansi.starts_with("\x1b[47m  \x1b[47m  \x1b[40m  "));
+        assert!(explore(&["crt", "png"], input()).is_err());
+    }
+
+    #[test]
+    fn custom_screen_and_schedule() {
+        let input = || Day10::EXAMPLE.trim().lines().map(String::from);
+        // a 1-pixel sprite is only drawn where the beam meets X exactly
+        let text = explore(&["crt", "text", "height=3", "sprite=1"], input()).unwrap();
+        let expected = [
+            "",
+            " #   #  #   #    #                      ",
+            "  #     #    #     #      #     #    #  ",
+            " #       # #     #        #      #      ",
+        ];
+        assert_eq!(text.split('\n').collect::<Vec<_>>(), expected);
+        // a 5-pixel sprite on two rows
+        let pbm = explore(&["crt", "pbm", "sprite=5", "height=2"], input()).unwrap();
+        let expected = [
+            "P1",
+            "40 2",
+            "1 1 0 0 1 1 0 0 1 1 0 1 1 1 0 0 1 1 0 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 0",
+            "1 1 1 0 0 1 1 1 1 0 0 1 1 1 1 0 0 1 1 1 1 0 0 1 1 1 1 0 0 1 1 1 1 0 0 1 1 1 1 1",
+        ];
+        assert_eq!(pbm.lines().collect::<Vec<_>>(), expected);
+        // pixels past the end of a small screen are dropped
+        assert_eq!(
+            explore(&["crt", "text", "width=3", "height=1"], input()).unwrap(),
+            "\n## "
+        );
+        for opts in [["width=x"], ["depth=2"], ["width"]] {
+            assert!(explore(&["crt", "text", opts[0]], input()).is_err());
         }
-        Ok(Str(std::borrow::Cow::Owned(out)))
+        let huge = format!("width={}", usize::MAX);
+        assert!(explore(&["crt", "text", &huge, "height=2"], input()).is_err());
+
+        let signal = |opts: &[&str]| {
+            let args: Vec<&str> = iter::once("signal").chain(opts.iter().copied()).collect();
+            explore(&args, input()).unwrap()
+        };
+        assert_eq!(signal(&[]), "13140\n");
+        assert_eq!(signal(&["first=20", "every=40"]), "13140\n");
+        // only cycle 20 is sampled, with X = 21
+        assert_eq!(signal(&["every=0"]), "420\n");
+        assert_eq!(
+            signal(&["first=1", "every=1"]),
+            signal(&["every=1", "first=1"])
+        );
+        assert!(explore(&["signal", "every=-1"], input()).is_err());
     }
 }
@@ -1,10 +1,17 @@
-use std::{cmp::Reverse, collections::VecDeque};
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    fmt::{self, Display, Write},
+    str::FromStr,
+};
 
 use aoc_framework::{
-    anyhow::{anyhow, Context},
+    anyhow::{anyhow, bail, Context},
     *,
 };
 
+use crate::helpers::{blocks, ints, scan, scan_parse, Checked, UInt};
+
 pub struct Day11;
 
 impl_day!(Day11::{Part1, Part2}: 2022[11], r"
@@ -38,23 +45,189 @@ Monkey 3:
 ");
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum Operand {
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinOp {
+    fn symbol(self) -> char {
+        match self {
+            BinOp::Add => '+',
+            BinOp::Sub => '-',
+            BinOp::Mul => '*',
+            BinOp::Div => '/',
+            BinOp::Rem => '%',
+        }
+    }
+}
+
+/// Right-hand side of an `Operation: new = ...` line
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Expr {
     Old,
-    Const(u64),
+    Const(UInt),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum Operation {
-    Add,
-    Mul,
+impl Expr {
+    // `UInt` is only `Copy` without the `bigint` feature
+    #[allow(clippy::clone_on_copy)]
+    fn eval(&self, old: &UInt) -> anyhow::Result<UInt> {
+        match self {
+            Expr::Old => Ok(old.clone()),
+            Expr::Const(n) => Ok(n.clone()),
+            Expr::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval(old)?, rhs.eval(old)?);
+                match op {
+                    BinOp::Add => lhs.try_add(&rhs),
+                    BinOp::Sub => lhs.try_sub(&rhs),
+                    BinOp::Mul => lhs.try_mul(&rhs),
+                    BinOp::Div => lhs.try_div(&rhs),
+                    BinOp::Rem => lhs.try_rem(&rhs),
+                }
+            }
+        }
+    }
+
+    /// Whether the expression only uses `+`, `-` and `*`, which commute with
+    /// reducing every value modulo some number
+    fn is_polynomial(&self) -> bool {
+        match self {
+            Expr::Old | Expr::Const(_) => true,
+            Expr::Binary(_, BinOp::Div | BinOp::Rem, _) => false,
+            Expr::Binary(lhs, _, rhs) => lhs.is_polynomial() && rhs.is_polynomial(),
+        }
+    }
+
+    /// Evaluates a polynomial expression modulo `m`, keeping every
+    /// intermediate value below `m`
+    fn eval_mod(&self, old: &UInt, m: &UInt) -> anyhow::Result<UInt> {
+        match self {
+            Expr::Old => old.try_rem(m),
+            Expr::Const(n) => n.try_rem(m),
+            Expr::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval_mod(old, m)?, rhs.eval_mod(old, m)?);
+                match op {
+                    BinOp::Add => lhs.try_add(&rhs)?.try_rem(m),
+                    // add `m` first so that the difference cannot go below zero
+                    BinOp::Sub => lhs.try_add(m)?.try_sub(&rhs)?.try_rem(m),
+                    BinOp::Mul => lhs.try_mul(&rhs)?.try_rem(m),
+                    BinOp::Div | BinOp::Rem => bail!("Cannot reduce {self} modulo {m}"),
+                }
+            }
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Old => write!(f, "old"),
+            Expr::Const(n) => write!(f, "{n}"),
+            Expr::Binary(lhs, op, rhs) => write!(f, "({lhs} {} {rhs})", op.symbol()),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut parser = ExprParser { s, pos: 0 };
+        let expr = parser.sum()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(_) => Err(parser.unexpected("an operator")),
+        }
+    }
+}
+
+/// Recursive descent parser for [`Expr`], with the usual precedence and left
+/// associativity
+struct ExprParser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl ExprParser<'_> {
+    fn peek(&mut self) -> Option<u8> {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+        self.s.as_bytes().get(self.pos).copied()
+    }
+
+    fn unexpected(&mut self, expected: &str) -> anyhow::Error {
+        let found = match self.s[self.pos..].chars().next() {
+            Some(c) => format!("{c:?}"),
+            None => "the end of the expression".to_string(),
+        };
+        anyhow!(
+            "Expected {expected} at column {}, found {found}",
+            self.pos + 1
+        )
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[BinOp],
+        operand: fn(&mut Self) -> anyhow::Result<Expr>,
+    ) -> anyhow::Result<Expr> {
+        let mut lhs = operand(self)?;
+        while let Some(&op) = self
+            .peek()
+            .and_then(|c| ops.iter().find(|op| op.symbol() as u8 == c))
+        {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(operand(self)?));
+        }
+        Ok(lhs)
+    }
+
+    fn sum(&mut self) -> anyhow::Result<Expr> {
+        self.binary(&[BinOp::Add, BinOp::Sub], Self::product)
+    }
+
+    fn product(&mut self) -> anyhow::Result<Expr> {
+        self.binary(&[BinOp::Mul, BinOp::Div, BinOp::Rem], Self::atom)
+    }
+
+    fn atom(&mut self) -> anyhow::Result<Expr> {
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                let expr = self.sum()?;
+                if self.peek() != Some(b')') {
+                    return Err(self.unexpected("')'"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(b'0'..=b'9') => {
+                let rest = &self.s[self.pos..];
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                Ok(Expr::Const(rest[..len].parse()?))
+            }
+            _ if self.s[self.pos..].starts_with("old") => {
+                self.pos += 3;
+                Ok(Expr::Old)
+            }
+            _ => Err(self.unexpected("a number, `old` or '('")),
+        }
+    }
 }
 
 #[derive(Debug)]
 struct Monkey {
-    items: VecDeque<u64>,
-    operation: Operation,
-    operand: Operand,
-    div_test: u64,
+    items: VecDeque<UInt>,
+    operation: Expr,
+    div_test: UInt,
     target_true: usize,
     target_false: usize,
 
@@ -62,86 +235,129 @@ struct Monkey {
 }
 
 impl Monkey {
-    fn run_op(&self, item: u64) -> u64 {
-        let value = match self.operand {
-            Operand::Old => item,
-            Operand::Const(n) => n,
-        };
-        match self.operation {
-            Operation::Add => item + value,
-            Operation::Mul => item * value,
-        }
-    }
-
-    fn run_turn(&mut self) -> Option<(u64, usize)> {
-        let item = self.items.pop_front()?;
-        self.inspected += 1;
-        let new_item = self.run_op(item) / 3;
-        let target = if new_item % self.div_test == 0 {
+    fn target(&self, item: &UInt) -> anyhow::Result<usize> {
+        Ok(if item.try_rem(&self.div_test)?.is_zero() {
             self.target_true
         } else {
             self.target_false
-        };
-        Some((new_item, target))
+        })
     }
 
-    fn run_turn_2(&mut self, divisor: u64) -> Option<(u64, usize)> {
-        let item = self.items.pop_front()?;
-        self.inspected += 1;
-        let new_item = self.run_op(item) % divisor;
-        let target = if new_item % self.div_test == 0 {
-            self.target_true
-        } else {
-            self.target_false
+    /// New worry level of an item after inspection and relief, and the
+    /// monkey it is thrown to
+    fn inspect(&self, item: &UInt, relief: &Relief) -> anyhow::Result<(UInt, usize)> {
+        let new_item = relief
+            .apply(&self.operation, item)
+            .with_context(|| format!("inspecting item {item}"))?;
+        let target = self.target(&new_item)?;
+        Ok((new_item, target))
+    }
+
+    fn run_turn(&mut self, relief: &Relief) -> anyhow::Result<Option<(UInt, usize)>> {
+        let Some(item) = self.items.pop_front() else {
+            return Ok(None);
         };
-        Some((new_item, target))
-    }
-}
-
-fn parse_monkey(mut input: impl Iterator<Item = String>) -> anyhow::Result<Monkey> {
-    input.next();
-    let items: VecDeque<u64> = input
-        .next()
-        .ok_or_else(|| anyhow!("Invalid input"))?
-        .split_once(": ")
-        .ok_or_else(|| anyhow!("Invalid input"))?
-        .1
-        .split(", ")
-        .map(|item| item.parse().context("Invalid input"))
-        .collect::<anyhow::Result<_>>()?;
-    let (operation, operand) = input
-        .next()
-        .and_then(|line| {
-            let (op, operand) = line.split_once("= old ")?.1.split_once(' ')?;
-            let op = match op {
-                "*" => Operation::Mul,
-                "+" => Operation::Add,
-                _ => return None,
-            };
-            let operand = match operand {
-                "old" => Operand::Old,
-                _ => Operand::Const(operand.parse().ok()?),
-            };
-            Some((op, operand))
+        self.inspected += 1;
+        self.inspect(&item, relief).map(Some)
+    }
+}
+
+/// How worry levels are kept in check after each inspection
+#[derive(Debug)]
+enum Relief {
+    /// Divide by `k`, rounding down
+    DivideBy(UInt),
+    /// Keep worry levels modulo `m`, which leaves tests unchanged as long as
+    /// `m` is a multiple of every monkey's test and operations are polynomials
+    Modulo(UInt),
+    /// [`Relief::Modulo`] the least common multiple of the monkeys' tests
+    ModuloLcm,
+    None,
+    /// Any expression of the new worry level, written as `old` like in the
+    /// monkeys' operations
+    Custom(Expr),
+}
+
+impl FromStr for Relief {
+    type Err = anyhow::Error;
+
+    /// Parses `div:K`, `mod:M`, `lcm`, `none` or `expr:EXPR`
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s.split_once(':') {
+            Some(("div", k)) => Relief::DivideBy(k.parse()?),
+            Some(("mod", m)) => Relief::Modulo(m.parse()?),
+            Some(("expr", expr)) => Relief::Custom(expr.parse()?),
+            None if s == "lcm" => Relief::ModuloLcm,
+            None if s == "none" => Relief::None,
+            _ => {
+                bail!("Invalid relief policy {s:?}, expected div:K, mod:M, lcm, none or expr:EXPR")
+            }
         })
-        .ok_or_else(|| anyhow!("Invalid input"))?;
-    let div_test: u64 = input
-        .next()
-        .and_then(|line| line.rsplit_once(' ')?.1.parse().ok())
-        .ok_or_else(|| anyhow!("Invalid input"))?;
-    let target_true: usize = input
-        .next()
-        .and_then(|line| line.rsplit_once(' ')?.1.parse().ok())
-        .ok_or_else(|| anyhow!("Invalid input"))?;
-    let target_false: usize = input
-        .next()
-        .and_then(|line| line.rsplit_once(' ')?.1.parse().ok())
-        .ok_or_else(|| anyhow!("Invalid input"))?;
-    input.next(); // empty line
+    }
+}
+
+impl Relief {
+    /// Turns [`Relief::ModuloLcm`] into a [`Relief::Modulo`] for these monkeys,
+    /// and checks that reducing worry levels is valid for their operations
+    fn resolve(self, monkeys: &[Monkey]) -> anyhow::Result<Relief> {
+        let relief = match self {
+            Relief::ModuloLcm => {
+                let lcm = monkeys.iter().try_fold(UInt::from_i64(1)?, |acc, m| {
+                    acc.try_div(&gcd(&acc, &m.div_test)?)?.try_mul(&m.div_test)
+                })?;
+                Relief::Modulo(lcm)
+            }
+            relief => relief,
+        };
+        if matches!(relief, Relief::Modulo(_)) {
+            if let Some(m) = monkeys.iter().find(|m| !m.operation.is_polynomial()) {
+                bail!(
+                    "new = {} uses / or %, so worry levels cannot be reduced",
+                    m.operation
+                );
+            }
+        }
+        Ok(relief)
+    }
+
+    fn apply(&self, operation: &Expr, item: &UInt) -> anyhow::Result<UInt> {
+        match self {
+            Relief::DivideBy(k) => operation.eval(item)?.try_div(k),
+            Relief::Modulo(m) => operation.eval_mod(item, m),
+            Relief::ModuloLcm => bail!("Relief policy used before being resolved"),
+            Relief::None => operation.eval(item),
+            Relief::Custom(expr) => expr.eval(&operation.eval(item)?),
+        }
+    }
+}
+
+#[allow(clippy::clone_on_copy)]
+fn gcd(a: &UInt, b: &UInt) -> anyhow::Result<UInt> {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while !b.is_zero() {
+        let rem = a.try_rem(&b)?;
+        a = b;
+        b = rem;
+    }
+    Ok(a)
+}
+
+fn parse_monkey(block: &[String]) -> anyhow::Result<Monkey> {
+    let [_, items, operation, div_test, target_true, target_false] = block else {
+        bail!("Invalid monkey: expected 6 lines, found {}", block.len());
+    };
+    let [items] = scan("Starting items: {}", items.trim())?;
+    let items = ints(items).collect::<anyhow::Result<_>>()?;
+    let [operation] = scan("Operation: new = {}", operation.trim())?;
+    let operation = operation
+        .parse()
+        .with_context(|| format!("parsing operation {operation:?}"))?;
+    let [div_test] = scan_parse("Test: divisible by {}", div_test.trim())?;
+    let [target_true] = scan_parse("If true: throw to monkey {}", target_true.trim())?;
+    let [target_false] = scan_parse("If false: throw to monkey {}", target_false.trim())?;
     Ok(Monkey {
         items,
         operation,
-        operand,
         div_test,
         target_true,
         target_false,
@@ -149,33 +365,248 @@ fn parse_monkey(mut input: impl Iterator<Item = String>) -> anyhow::Result<Monke
     })
 }
 
-pub struct Part1;
+fn parse_monkeys(input: impl Iterator<Item = String>) -> anyhow::Result<Vec<Monkey>> {
+    blocks(input)
+        .enumerate()
+        .map(|(i, block)| parse_monkey(&block).with_context(|| format!("monkey {i}")))
+        .collect()
+}
 
-impl Part for Part1 {
-    type D = Day11;
-    const N: u8 = 1;
-    const EXAMPLE_RESULT: Option<Answer> = Some(Num(10605));
+/// An item thrown during a round, with its worry level at that time
+#[derive(Clone, Debug)]
+pub struct Throw {
+    pub round: usize,
+    pub from: usize,
+    pub to: usize,
+    pub item: UInt,
+}
 
-    fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let mut input = input.peekable();
-        let mut monkeys: Vec<Monkey> = Vec::new();
-        while input.peek().is_some() {
-            let monkey = parse_monkey(&mut input)?;
-            monkeys.push(monkey);
-        }
-        for _ in 0..20 {
-            for i in 0..monkeys.len() {
-                while let Some((item, target)) = monkeys[i].run_turn() {
-                    monkeys[target].items.push_back(item);
+impl Display for Throw {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "round {}: monkey {} throws {} to monkey {}",
+            self.round, self.from, self.item, self.to
+        )
+    }
+}
+
+/// Inspections made during a round and items held at its end, per monkey
+#[derive(Clone, Debug)]
+pub struct RoundStats {
+    pub inspected: Vec<u64>,
+    pub held: Vec<usize>,
+}
+
+/// Rounds of keep away, keeping per-round statistics and optionally a trace
+/// of every throw
+struct KeepAway {
+    monkeys: Vec<Monkey>,
+    relief: Relief,
+    trace: Option<Vec<Throw>>,
+    stats: Vec<RoundStats>,
+}
+
+impl KeepAway {
+    fn new(monkeys: Vec<Monkey>, relief: Relief, trace: bool) -> anyhow::Result<Self> {
+        Ok(KeepAway {
+            relief: relief.resolve(&monkeys)?,
+            monkeys,
+            trace: trace.then(Vec::new),
+            stats: Vec::new(),
+        })
+    }
+
+    #[allow(clippy::clone_on_copy)]
+    fn play_round(&mut self) -> anyhow::Result<()> {
+        let round = self.stats.len() + 1;
+        let before: Vec<u64> = self.monkeys.iter().map(|m| m.inspected).collect();
+        for i in 0..self.monkeys.len() {
+            while let Some((item, target)) = self.monkeys[i]
+                .run_turn(&self.relief)
+                .with_context(|| format!("monkey {i}"))?
+            {
+                if let Some(trace) = &mut self.trace {
+                    trace.push(Throw {
+                        round,
+                        from: i,
+                        to: target,
+                        item: item.clone(),
+                    });
                 }
+                self.monkeys[target].items.push_back(item);
             }
         }
-        let mut counts = monkeys
+        self.stats.push(RoundStats {
+            inspected: self
+                .monkeys
+                .iter()
+                .zip(before)
+                .map(|(m, before)| m.inspected - before)
+                .collect(),
+            held: self.monkeys.iter().map(|m| m.items.len()).collect(),
+        });
+        Ok(())
+    }
+
+    fn monkey_business(&self) -> u64 {
+        let mut counts = self
+            .monkeys
             .iter()
             .map(|m| Reverse(m.inspected))
             .collect::<Vec<_>>();
         counts.sort_unstable();
-        Ok(Num(counts[0].0 * counts[1].0))
+        counts.iter().take(2).map(|count| count.0).product()
+    }
+
+    /// Every throw so far, if tracing was enabled
+    fn trace(&self) -> Option<&[Throw]> {
+        self.trace.as_deref()
+    }
+
+    /// One line per round with `inspected/held` for each monkey
+    fn table(&self) -> String {
+        let header = (0..self.monkeys.len()).map(|i| format!("{:>11}", format!("monkey {i}")));
+        let mut out = format!("round {}\n", header.format(" "));
+        for (round, stats) in self.stats.iter().enumerate() {
+            let cells = stats
+                .inspected
+                .iter()
+                .zip(&stats.held)
+                .map(|(inspected, held)| format!("{:>11}", format!("{inspected}/{held}")));
+            out += &format!("{:>5} {}\n", round + 1, cells.format(" "));
+        }
+        out
+    }
+}
+
+/// Inspections per monkey after `rounds` rounds, without simulating them all.
+///
+/// Items never interact, so each one is followed on its own until its monkey
+/// and worry level at the start of a round repeat, which `inspect` must
+/// guarantee by keeping worry levels bounded. The rest of its journey is then
+/// a repetition of that cycle.
+#[allow(clippy::clone_on_copy)]
+fn extrapolate(monkeys: &[Monkey], rounds: usize, relief: Relief) -> anyhow::Result<Vec<u64>> {
+    let relief = relief.resolve(monkeys)?;
+    let n = monkeys.len();
+    let mut total = vec![0; n];
+    let items = monkeys
+        .iter()
+        .enumerate()
+        .flat_map(|(i, m)| m.items.iter().map(move |item| (i, item.clone())));
+    for mut state in items {
+        // inspections per monkey made on the item during its first `r` rounds
+        let mut counts: Vec<Vec<u64>> = vec![vec![0; n]];
+        let mut seen: HashMap<(usize, UInt), usize> = HashMap::new();
+        let counted = loop {
+            let r = counts.len() - 1;
+            if r == rounds {
+                break counts[r].clone();
+            }
+            if let Some(&first) = seen.get(&state) {
+                let (cycles, rest) = ((rounds - r) / (r - first), (rounds - r) % (r - first));
+                break (0..n)
+                    .map(|m| {
+                        let per_cycle = counts[r][m] - counts[first][m];
+                        let in_rest = counts[first + rest][m] - counts[first][m];
+                        (cycles as u64)
+                            .try_mul(&per_cycle)?
+                            .try_add(&counts[r][m])?
+                            .try_add(&in_rest)
+                    })
+                    .collect::<anyhow::Result<_>>()
+                    .with_context(|| format!("counting inspections over {rounds} rounds"))?;
+            }
+            seen.insert(state.clone(), r);
+            let mut row = counts[r].clone();
+            let (mut monkey, mut item) = state;
+            // an item thrown to a monkey that has yet to play moves again this round
+            loop {
+                row[monkey] += 1;
+                let (new_item, target) = monkeys[monkey]
+                    .inspect(&item, &relief)
+                    .with_context(|| format!("monkey {monkey}"))?;
+                item = new_item;
+                let again = target >= monkey;
+                monkey = target;
+                if !again {
+                    break;
+                }
+            }
+            state = (monkey, item);
+            counts.push(row);
+        };
+        for (t, c) in total.iter_mut().zip(counted) {
+            *t = t
+                .try_add(&c)
+                .with_context(|| format!("counting inspections over {rounds} rounds"))?;
+        }
+    }
+    Ok(total)
+}
+
+fn monkey_business(
+    input: impl Iterator<Item = String>,
+    relief: Relief,
+    rounds: usize,
+) -> anyhow::Result<Answer> {
+    let mut game = KeepAway::new(parse_monkeys(input)?, relief, false)?;
+    for _ in 0..rounds {
+        game.play_round()?;
+    }
+    Ok(Num(game.monkey_business()))
+}
+
+/// Exploration commands, see `main`. `RELIEF` is `div:K`, `mod:M`, `lcm`, `none`
+/// or `expr:EXPR`, with `EXPR` using `old` for the new worry level.
+/// - `run ROUNDS RELIEF`: monkey business after `ROUNDS` rounds
+/// - `trace ROUNDS RELIEF`: every throw made during the first `ROUNDS` rounds
+/// - `table ROUNDS RELIEF`: inspections and held items per monkey and round
+/// - `extrapolate ROUNDS RELIEF`: inspections per monkey after `ROUNDS` rounds,
+///   following each item until it repeats instead of playing every round
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    let (command, rounds, relief) = match args {
+        [command @ ("run" | "trace" | "table" | "extrapolate"), rounds, relief] => (
+            *command,
+            rounds.parse::<usize>()?,
+            relief.parse::<Relief>()?,
+        ),
+        _ => bail!("Expected `run`, `trace`, `table` or `extrapolate` followed by ROUNDS RELIEF"),
+    };
+    let monkeys = parse_monkeys(input)?;
+    let mut out = String::new();
+    if command == "extrapolate" {
+        for (i, inspected) in extrapolate(&monkeys, rounds, relief)?.iter().enumerate() {
+            writeln!(out, "monkey {i}: {inspected}")?;
+        }
+        return Ok(out);
+    }
+    let mut game = KeepAway::new(monkeys, relief, command == "trace")?;
+    for _ in 0..rounds {
+        game.play_round()?;
+    }
+    match command {
+        "run" => writeln!(out, "{}", game.monkey_business())?,
+        "trace" => {
+            for throw in game.trace().unwrap_or_default() {
+                writeln!(out, "{throw}")?;
+            }
+        }
+        _ => out = game.table(),
+    }
+    Ok(out)
+}
+
+pub struct Part1;
+
+impl Part for Part1 {
+    type D = Day11;
+    const N: u8 = 1;
+    const EXAMPLE_RESULT: Option<Answer> = Some(Num(10605));
+
+    fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
+        monkey_business(input, Relief::DivideBy(UInt::from_i64(3)?), 20)
     }
 }
 
@@ -187,26 +618,124 @@ impl Part for Part2 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(2713310158));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let mut input = input.peekable();
-        let mut monkeys: Vec<Monkey> = Vec::new();
-        let mut common_factor = 1;
-        while input.peek().is_some() {
-            let monkey = parse_monkey(&mut input)?;
-            common_factor *= monkey.div_test;
-            monkeys.push(monkey);
-        }
-        for _ in 0..10_000 {
-            for i in 0..monkeys.len() {
-                while let Some((item, target)) = monkeys[i].run_turn_2(common_factor) {
-                    monkeys[target].items.push_back(item);
-                }
+        monkey_business(input, Relief::ModuloLcm, 10_000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Vec<Monkey> {
+        parse_monkeys(Day11::EXAMPLE.trim().lines().map(String::from)).unwrap()
+    }
+
+    fn relief(s: &str) -> Relief {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn extrapolate_matches_play_round() {
+        for (policy, rounds) in [("lcm", 10_000), ("div:3", 20), ("lcm", 1), ("lcm", 0)] {
+            let mut game = KeepAway::new(example(), relief(policy), false).unwrap();
+            for _ in 0..rounds {
+                game.play_round().unwrap();
             }
+            let played: Vec<u64> = game.monkeys.iter().map(|m| m.inspected).collect();
+            let extrapolated = extrapolate(&example(), rounds, relief(policy)).unwrap();
+            assert_eq!(extrapolated, played, "{rounds} rounds with {policy}");
         }
-        let mut counts = monkeys
-            .iter()
-            .map(|m| Reverse(m.inspected))
-            .collect::<Vec<_>>();
-        counts.sort_unstable();
-        Ok(Num(counts[0].0 * counts[1].0))
+    }
+
+    #[test]
+    fn extrapolate_reports_overflow() {
+        // 10^15 rounds of inspections still fit in a u64, usize::MAX rounds do not
+        let rounds = 1_000_000_000_000_000;
+        let inspected = extrapolate(&example(), rounds, relief("lcm")).unwrap();
+        assert!(inspected.iter().all(|&count| count > 0));
+        let err = extrapolate(&example(), usize::MAX, relief("lcm")).unwrap_err();
+        assert!(format!("{err:#}").contains("overflow"), "{err:#}");
+        let input = Day11::EXAMPLE.trim().lines().map(String::from);
+        assert!(explore(&["extrapolate", "18446744073709551615", "lcm"], input).is_err());
+    }
+
+    #[test]
+    fn trace_and_table() {
+        let input = || Day11::EXAMPLE.trim().lines().map(String::from);
+        let trace = explore(&["trace", "1", "div:3"], input()).unwrap();
+        let mut throws = trace.lines();
+        assert_eq!(
+            throws.next(),
+            Some("round 1: monkey 0 throws 500 to monkey 3")
+        );
+        assert_eq!(
+            throws.next(),
+            Some("round 1: monkey 0 throws 620 to monkey 3")
+        );
+        assert_eq!(
+            throws.last(),
+            Some("round 1: monkey 3 throws 1046 to monkey 1")
+        );
+
+        let table = explore(&["table", "2", "div:3"], input()).unwrap();
+        let expected = [
+            "round    monkey 0    monkey 1    monkey 2    monkey 3",
+            "    1         2/4         4/6         3/0         5/0",
+            "    2         4/5         6/5         1/0         5/0",
+        ];
+        assert_eq!(table.lines().collect::<Vec<_>>(), expected);
+
+        let inspected = explore(&["extrapolate", "10000", "lcm"], input()).unwrap();
+        let expected = "monkey 0: 52166\nmonkey 1: 47830\nmonkey 2: 1938\nmonkey 3: 52013\n";
+        assert_eq!(inspected, expected);
+        assert!(explore(&["trace", "1"], input()).is_err());
+    }
+
+    #[test]
+    fn relief_is_chosen_per_run() {
+        let input = || Day11::EXAMPLE.trim().lines().map(String::from);
+        let run = |rounds, relief| explore(&["run", rounds, relief], input()).unwrap();
+        assert_eq!(run("20", "div:3"), "10605\n");
+        assert_eq!(run("10000", "lcm"), "2713310158\n");
+        // the LCM of the example's tests is 96577
+        assert_eq!(run("10000", "mod:96577"), "2713310158\n");
+        assert_eq!(run("20", "lcm"), "10197\n");
+        let err = explore(&["run", "20", "sqrt"], input()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected div:K, mod:M, lcm, none or expr:EXPR"));
+        assert!(explore(&["trace", "1", "div"], input()).is_err());
+    }
+
+    #[test]
+    fn custom_relief() {
+        let input = || Day11::EXAMPLE.trim().lines().map(String::from);
+        let run = |rounds, relief| explore(&["run", rounds, relief], input()).unwrap();
+        assert_eq!(
+            run("20", "expr:old / 3"),
+            "10605
+"
+        );
+        assert_eq!(
+            run("10000", "expr:old % 96577"),
+            "2713310158
+"
+        );
+        assert_eq!(
+            run("20", "expr:old % (2 * 96577)"),
+            "10197
+"
+        );
+        // dividing by 3 twice then by 2, rounding down each time, is dividing by 18
+        let game = |relief: &str| {
+            let mut game = KeepAway::new(example(), relief.parse().unwrap(), true).unwrap();
+            game.play_round().unwrap();
+            game.trace().unwrap()[0].to_string()
+        };
+        assert_eq!(game("expr:old / 3 / 3 / 2"), game("div:18"));
+        let err = "expr:old ^ 2".parse::<Relief>().unwrap_err();
+        assert!(err.to_string().contains("column 5"), "{err}");
+        let err = explore(&["run", "20", "expr:old / 0"], input()).unwrap_err();
+        assert!(format!("{err:#}").contains("monkey 0"), "{err:#}");
     }
 }
@@ -1,4 +1,9 @@
-use aoc_framework::*;
+use std::{fmt::Write, iter};
+
+use aoc_framework::{
+    anyhow::{anyhow, bail},
+    *,
+};
 
 pub struct Day12;
 
@@ -61,21 +66,57 @@ impl Grid {
     }
 }
 
-pub struct Part1;
+/// Shortest route found by one of the parts
+#[derive(Debug)]
+pub struct Route {
+    // cells from the start to the end of the route as `(x, y)`, with their elevation
+    cells: Vec<(usize, usize)>,
+    profile: Vec<u8>,
+}
 
-impl Part for Part1 {
-    type D = Day12;
-    const N: u8 = 1;
-    const EXAMPLE_RESULT: Option<Answer> = Some(Num(31));
+impl Route {
+    pub fn steps(&self) -> usize {
+        self.cells.len().saturating_sub(1)
+    }
 
-    fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let grid = Grid::parse(input);
-        let len = grid.data.len();
-        let source = grid
-            .data
+    pub fn cells(&self) -> &[(usize, usize)] {
+        &self.cells
+    }
+
+    pub fn start(&self) -> Option<(usize, usize)> {
+        self.cells.first().copied()
+    }
+
+    /// Elevation of each cell along the route, `a` being 0
+    pub fn profile(&self) -> &[u8] {
+        &self.profile
+    }
+}
+
+impl Grid {
+    fn find(&self, tile: Tile) -> anyhow::Result<usize> {
+        self.data
             .iter()
-            .position(|&tile| tile == Tile::Start)
-            .unwrap();
+            .position(|&t| t == tile)
+            .ok_or_else(|| anyhow!("No {tile:?} tile"))
+    }
+
+    fn route(&self, cells: impl Iterator<Item = usize>) -> Route {
+        let (cells, profile) = cells
+            .map(|pos| ((pos % self.w, pos / self.w), self.data[pos].elevation()))
+            .unzip();
+        Route { cells, profile }
+    }
+
+    /// Dijkstra from `source`, climbing forwards when `uphill`, or following
+    /// moves backwards otherwise. Stops early once `target` is reached.
+    fn shortest_paths(
+        &self,
+        source: usize,
+        uphill: bool,
+        target: Option<usize>,
+    ) -> (Vec<u64>, Vec<Option<usize>>) {
+        let len = self.data.len();
         let mut dist = vec![u64::MAX; len];
         dist[source] = 0;
         let mut prev = vec![None; len];
@@ -91,52 +132,130 @@ impl Part for Part1 {
                 .0;
             q[pos] = false;
             unvisited -= 1;
-            if grid.data[pos] == Tile::End {
-                let mut p = pos;
-                let mut path_len = 0;
-                // let mut path = Vec::new();
-                while let Some(previous) = prev[p] {
-                    // path.push(previous);
-                    path_len += 1;
-                    p = previous;
-                }
-                // for y in 0..grid.h {
-                //     for x in 0..grid.w {
-                //         let pos = x + y * grid.w;
-                //         if path.contains(&pos) {
-                //             print!("#");
-                //         } else {
-                //             print!("{}", match grid.data[pos] {
-                //                 Tile::Start => 'S',
-                //                 Tile::End => 'E',
-                //                 Tile::Height(h) => (b'a' + h) as char,
-                //             })
-                //         }
-                //     }
-                //     println!();
-                // }
-                return Ok(Num(path_len as u64));
+            if Some(pos) == target {
+                break;
             }
-            let x = pos % grid.w;
-            let y = pos / grid.w;
+            let x = pos % self.w;
+            let y = pos / self.w;
             for (dx, dy) in [(1, 0), (0, 1), (-1, 0), (0, -1)] {
                 let nx = x as isize - dx;
                 let ny = y as isize - dy;
-                if nx < 0 || ny < 0 || nx >= grid.w as isize || ny >= grid.h as isize {
+                if nx < 0 || ny < 0 || nx >= self.w as isize || ny >= self.h as isize {
                     continue;
                 }
-                let npos = nx as usize + ny as usize * grid.w;
+                let npos = nx as usize + ny as usize * self.w;
                 if !q[npos] {
                     continue;
                 }
-                let alt = dist[pos] + grid.data[pos].dist(grid.data[npos]);
+                let cost = if uphill {
+                    self.data[pos].dist(self.data[npos])
+                } else {
+                    self.data[npos].dist(self.data[pos])
+                };
+                let alt = dist[pos] + cost;
                 if alt < dist[npos] {
                     dist[npos] = alt;
                     prev[npos] = Some(pos);
                 }
             }
         }
-        Ok(Num(0))
+        (dist, prev)
+    }
+
+    /// Shortest route from `S` to `E`
+    fn route_from_start(&self) -> anyhow::Result<Route> {
+        let (source, target) = (self.find(Tile::Start)?, self.find(Tile::End)?);
+        let (_, prev) = self.shortest_paths(source, true, Some(target));
+        let mut cells = vec![target];
+        while let Some(previous) = prev[*cells.last().unwrap()] {
+            cells.push(previous);
+        }
+        Ok(self.route(cells.into_iter().rev()))
+    }
+
+    /// Shortest route to `E` from any cell of elevation `a`
+    fn best_trail(&self) -> anyhow::Result<Route> {
+        let (dist, prev) = self.shortest_paths(self.find(Tile::End)?, false, None);
+        let start = (0..self.data.len())
+            .filter(|&pos| self.data[pos].elevation() == 0)
+            .min_by_key(|&pos| dist[pos])
+            .ok_or_else(|| anyhow!("No cell of elevation a"))?;
+        // paths were searched from the end, so following them leads there
+        Ok(self.route(iter::successors(Some(start), |&pos| prev[pos])))
+    }
+
+    /// Heightmap with the route drawn over it as in the puzzle text: arrows
+    /// point to the next cell and `E` marks the end.
+    fn render(&self, route: &Route) -> String {
+        let mut out: Vec<Vec<char>> = self
+            .data
+            .chunks(self.w)
+            .map(|row| {
+                row.iter()
+                    .map(|tile| match tile {
+                        Tile::Start => 'S',
+                        Tile::End => 'E',
+                        Tile::Height(h) => (b'a' + h) as char,
+                    })
+                    .collect()
+            })
+            .collect();
+        for pair in route.cells().windows(2) {
+            let [(x, y), (nx, ny)] = [pair[0], pair[1]];
+            out[y][x] = match (nx as isize - x as isize, ny as isize - y as isize) {
+                (1, 0) => '>',
+                (-1, 0) => '<',
+                (0, 1) => 'v',
+                _ => '^',
+            };
+        }
+        if let Some(&(x, y)) = route.cells().last() {
+            out[y][x] = 'E';
+        }
+        out.into_iter()
+            .map(|row| row.into_iter().chain(['\n']).collect::<String>())
+            .collect()
+    }
+}
+
+/// Exploration commands, see `main`. `ROUTE` is `start` for the route from
+/// `S`, or `trail` for the shortest one from any cell of elevation `a`.
+/// - `map ROUTE`: the heightmap with the route drawn over it
+/// - `profile ROUTE`: elevations along the route
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    let grid = Grid::parse(input);
+    let (command, route) = match args {
+        [command @ ("map" | "profile"), "start"] => (*command, grid.route_from_start()?),
+        [command @ ("map" | "profile"), "trail"] => (*command, grid.best_trail()?),
+        _ => bail!("Expected `map start|trail` or `profile start|trail`"),
+    };
+    let mut out = String::new();
+    if command == "map" {
+        out = grid.render(&route);
+        if let Some((x, y)) = route.start() {
+            writeln!(out, "{} steps from x={x}, y={y}", route.steps())?;
+        }
+    } else {
+        let profile: String = route
+            .profile()
+            .iter()
+            .map(|&h| (b'a' + h) as char)
+            .collect();
+        writeln!(out, "{profile}")?;
+    }
+    Ok(out)
+}
+
+pub struct Part1;
+
+impl Part for Part1 {
+    type D = Day12;
+    const N: u8 = 1;
+    const EXAMPLE_RESULT: Option<Answer> = Some(Num(31));
+
+    fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
+        let route = Grid::parse(input).route_from_start()?;
+        Ok(Num(route.steps() as u64))
     }
 }
 
@@ -148,54 +267,69 @@ impl Part for Part2 {
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(29));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let grid = Grid::parse(input);
-        let len = grid.data.len();
-        let source = grid
-            .data
-            .iter()
-            .position(|&tile| tile == Tile::End)
-            .unwrap();
-        let mut dist = vec![u64::MAX; len];
-        dist[source] = 0;
-        let mut prev = vec![None; len];
-        let mut unvisited = len;
-        let mut q = vec![true; len];
-        while unvisited > 0 {
-            let pos = dist
-                .iter()
-                .enumerate()
-                .filter(|(i, _)| q[*i])
-                .min_by_key(|(_, v)| *v)
-                .unwrap()
-                .0;
-            q[pos] = false;
-            unvisited -= 1;
-            let x = pos % grid.w;
-            let y = pos / grid.w;
-            for (dx, dy) in [(1, 0), (0, 1), (-1, 0), (0, -1)] {
-                let nx = x as isize - dx;
-                let ny = y as isize - dy;
-                if nx < 0 || ny < 0 || nx >= grid.w as isize || ny >= grid.h as isize {
-                    continue;
-                }
-                let npos = nx as usize + ny as usize * grid.w;
-                if !q[npos] {
-                    continue;
-                }
-                let alt = dist[pos] + grid.data[npos].dist(grid.data[pos]);
-                if alt < dist[npos] {
-                    dist[npos] = alt;
-                    prev[npos] = Some(pos);
-                }
-            }
+        let route = Grid::parse(input).best_trail()?;
+        Ok(Num(route.steps() as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Grid {
+        Grid::parse(Day12::EXAMPLE.trim().lines().map(String::from))
+    }
+
+    /// Checks that a route only takes single steps and never climbs more
+    /// than one level at a time
+    fn assert_walkable(route: &Route) {
+        assert_eq!(route.cells().len(), route.profile().len());
+        for (pair, heights) in route.cells().windows(2).zip(route.profile().windows(2)) {
+            let [(x, y), (nx, ny)] = [pair[0], pair[1]];
+            assert_eq!(x.abs_diff(nx) + y.abs_diff(ny), 1, "{pair:?}");
+            assert!(heights[1] <= heights[0] + 1, "{pair:?} climbs {heights:?}");
         }
-        Ok(Num(grid
-            .data
-            .into_iter()
-            .zip(dist.into_iter())
-            .filter(|(tile, _)| tile.elevation() == 0)
-            .map(|(_, dist)| dist)
-            .min()
-            .unwrap()))
+    }
+
+    #[test]
+    fn route_from_start() {
+        let route = example().route_from_start().unwrap();
+        assert_walkable(&route);
+        assert_eq!(route.steps(), 31);
+        assert_eq!(route.start(), Some((0, 0)));
+        assert_eq!(route.cells().last(), Some(&(5, 2)));
+        assert_eq!(route.profile().first(), Some(&0));
+        assert_eq!(route.profile().last(), Some(&25));
+    }
+
+    #[test]
+    fn best_trail() {
+        let route = example().best_trail().unwrap();
+        assert_walkable(&route);
+        assert_eq!(route.steps(), 29);
+        assert_eq!(route.profile().first(), Some(&0));
+        assert_eq!(route.cells().last(), Some(&(5, 2)));
+    }
+
+    #[test]
+    fn render() {
+        let grid = example();
+        let map = grid.render(&grid.route_from_start().unwrap());
+        let expected = [">>vv<<<<", "abvvv<<^", "acvv>E^^", "acv>>>^^", "ab>>>>>^"];
+        assert_eq!(map, expected.join("\n") + "\n");
+    }
+
+    #[test]
+    fn explore_commands() {
+        let input = || Day12::EXAMPLE.trim().lines().map(String::from);
+        let map = explore(&["map", "start"], input()).unwrap();
+        assert!(map.ends_with("\n31 steps from x=0, y=0\n"), "{map}");
+        let profile = explore(&["profile", "trail"], input()).unwrap();
+        assert_eq!(profile.trim().len(), 30);
+        assert!(
+            profile.starts_with('a') && profile.ends_with("z\n"),
+            "{profile}"
+        );
+        assert!(explore(&["map", "end"], input()).is_err());
     }
 }
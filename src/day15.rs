@@ -1,6 +1,8 @@
 use std::str::FromStr;
 
-use aoc_framework::{anyhow::anyhow, *};
+use aoc_framework::*;
+
+use crate::helpers::{parse_lines, scan_parse};
 
 pub struct Day15;
 
@@ -33,23 +35,6 @@ impl Point {
     }
 }
 
-impl FromStr for Point {
-    type Err = anyhow::Error;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (x, y) = s
-            .split_once(", ")
-            .and_then(|(x, y)| {
-                x.strip_prefix("x=")
-                    .and_then(|x| y.strip_prefix("y=").map(|y| (x, y)))
-            })
-            .ok_or_else(|| anyhow!("Invalid input"))?;
-        Ok(Point {
-            x: x.parse()?,
-            y: y.parse()?,
-        })
-    }
-}
-
 #[derive(Debug)]
 struct Sensor {
     pos: Point,
@@ -60,16 +45,10 @@ struct Sensor {
 impl FromStr for Sensor {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (l, r) = s
-            .split_once(':')
-            .and_then(|(l, r)| {
-                l.rsplit("at ")
-                    .next()
-                    .and_then(|l| r.rsplit("at ").next().map(|r| (l, r)))
-            })
-            .ok_or_else(|| anyhow!("Invalid input"))?;
-        let pos: Point = l.parse()?;
-        let beacon: Point = r.parse()?;
+        let [x, y, bx, by] =
+            scan_parse("Sensor at x={}, y={}: closest beacon is at x={}, y={}", s)?;
+        let pos = Point { x, y };
+        let beacon = Point { x: bx, y: by };
         let beacon_dist = pos.dist(&beacon);
         Ok(Sensor {
             pos,
@@ -87,9 +66,7 @@ impl Part for Part1 {
     const EXAMPLE_RESULT: Option<Answer> = None; //Some(Num(26));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let sensors: Vec<_> = input
-            .map(|line| line.parse::<Sensor>())
-            .collect::<anyhow::Result<_>>()?;
+        let sensors: Vec<Sensor> = parse_lines(input, str::parse).collect::<anyhow::Result<_>>()?;
         let mut beacons = Vec::new();
         sensors.iter().for_each(|sensor| {
             if !beacons.contains(&sensor.beacon) {
@@ -134,9 +111,7 @@ impl Part for Part2 {
     const EXAMPLE_RESULT: Option<Answer> = None;
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let sensors: Vec<_> = input
-            .map(|line| line.parse::<Sensor>())
-            .collect::<anyhow::Result<_>>()?;
+        let sensors: Vec<Sensor> = parse_lines(input, str::parse).collect::<anyhow::Result<_>>()?;
         const LIMIT: i64 = 4_000_000;
         for y in 0..=LIMIT {
             let mut x = 0;
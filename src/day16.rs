@@ -1,7 +1,9 @@
-use aoc_framework::*;
-use std::{borrow::Cow, collections::HashSet, fmt::Write};
+use aoc_framework::{anyhow::bail, *};
+use std::fmt::Write;
 
-use fxhash::FxHashMap;
+use fxhash::{FxBuildHasher, FxHashMap};
+
+use crate::helpers::{parse_lines, scan, Memo};
 
 pub struct Day16;
 
@@ -38,10 +40,6 @@ impl std::fmt::Debug for StateId {
 }
 
 impl StateId {
-    fn is_open(self) -> bool {
-        self.0 >= OPEN_OFFSET
-    }
-
     fn open(self) -> Self {
         StateId(self.0 + OPEN_OFFSET)
     }
@@ -50,6 +48,8 @@ impl StateId {
 #[derive(Debug, Clone)]
 struct Adj {
     rate: u8,
+    // bit identifying this valve in `SearchState::opened`, 0 if it cannot be opened
+    mask: u64,
     dests: Vec<(StateId, u8)>,
 }
 
@@ -58,16 +58,16 @@ fn parse_id(s: &str) -> StateId {
     StateId((bytes[0] - b'A') as u16 * 26 + (bytes[1] - b'A') as u16)
 }
 
-#[derive(PartialEq, Eq, Hash)]
-struct SearchState<'a> {
-    opened: Cow<'a, Vec<StateId>>,
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+struct SearchState {
+    opened: u64,
     step: u8,
     current: StateId,
 }
 
-#[derive(PartialEq, Eq, Hash)]
-struct SearchState2<'a> {
-    opened: Cow<'a, Vec<StateId>>,
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+struct SearchState2 {
+    opened: u64,
     step: u8,
     current: [StateId; 2],
 }
@@ -76,29 +76,42 @@ struct SearchState2<'a> {
 struct Graph(fxhash::FxHashMap<StateId, Adj>, usize);
 
 impl Graph {
-    fn add_line(&mut self, line: &str) {
-        let mut parts = line.split([' ', ';', ',', '=']);
-        let id = parse_id(parts.nth(1).unwrap());
-        let rate = parts.nth(3).unwrap().parse().unwrap();
-        let mut dests: Vec<_> = parts
-            .skip(5)
-            .filter(|id| !id.is_empty())
+    fn add_line(&mut self, line: &str) -> anyhow::Result<()> {
+        let [id, rate, tunnels] = scan("Valve {} has flow rate={}; {}", line)?;
+        let id = parse_id(id);
+        let rate = rate.parse()?;
+        // "tunnels lead to valves AA, BB" or "tunnel leads to valve AA"
+        let mut dests: Vec<_> = tunnels
+            .trim_start_matches(|c: char| !c.is_ascii_uppercase())
+            .split(", ")
             .map(parse_id)
             .map(|id| (id, 1))
             .collect();
         if rate > 0 {
+            if self.1 == u64::BITS as usize {
+                bail!("Too many valves with a non-zero flow rate");
+            }
             let open_id = id.open();
             self.0.insert(
                 open_id,
                 Adj {
                     rate,
+                    mask: 1 << self.1,
                     dests: dests.clone(),
                 },
             );
             dests.push((open_id, 1));
             self.1 += 1;
         }
-        self.0.insert(id, Adj { rate: 0, dests });
+        self.0.insert(
+            id,
+            Adj {
+                rate: 0,
+                mask: 0,
+                dests,
+            },
+        );
+        Ok(())
     }
 
     fn shortest_path(&self, s1: StateId, s2: StateId) -> u8 {
@@ -157,119 +170,166 @@ impl Graph {
             .for_each(|adj| adj.dests.retain(|(id, _)| ids.contains(id)));
     }
 
-    fn bfs<'a, 'b: 'a>(
+    fn is_opened(&self, opened: u64, id: StateId) -> bool {
+        opened & self.0[&id].mask != 0
+    }
+
+    fn bfs(
         &self,
         step: u8,
         flow: u64,
         state: StateId,
-        visited: Cow<'b, Vec<StateId>>,
-        memo: &'a mut FxHashMap<SearchState<'b>, (u64, Vec<(u8, StateId)>)>,
-    ) -> (u64, Vec<(u8, StateId)>) {
-        if visited.len() == self.1 {
-            return (flow * (STEPS_PT1 + 1 - step) as u64, vec![(step, state)]);
+        opened: u64,
+        memo: &mut Memo<SearchState, (u64, Option<StateId>), FxBuildHasher>,
+    ) -> u64 {
+        if opened.count_ones() as usize == self.1 {
+            return flow * (STEPS_PT1 + 1 - step) as u64;
         }
-        let opened = visited.clone();
         let search_state = SearchState {
             current: state,
             step,
             opened,
         };
-        if let Some((flow, path)) = memo.get(&search_state) {
-            return (*flow, path.clone());
-        }
-        let s = self.0.get(&state).unwrap();
-        if step >= STEPS_PT1 {
-            memo.insert(search_state, (flow, vec![(step, state)]));
-            return (flow, vec![(step, state)]);
-        }
-        let mut visited = visited.clone();
-        if state.is_open() {
-            visited.to_mut().push(state);
-        }
-
-        let new_flow = flow + s.rate as u64;
-        let best_flow_dist = (STEPS_PT1 - step + 1) as u64;
-        let mut best_flow = flow + new_flow * (best_flow_dist - 1) as u64;
-        let mut best_path = Vec::new();
-        for (dest, dist) in &s.dests {
-            if visited.contains(dest) || step + dist > STEPS_PT1 {
-                continue;
+        let (best_flow, _) = memo.get_or_insert_with(search_state, |memo| {
+            if step >= STEPS_PT1 {
+                return (flow, None);
             }
-            let (next_flow, next_path) =
-                self.bfs(step + dist, new_flow, *dest, visited.clone(), memo);
-            let next_flow = flow + new_flow * (dist - 1) as u64 + next_flow;
-            if next_flow >= best_flow {
-                best_flow = next_flow;
-                best_path = next_path;
+            let s = &self.0[&state];
+            let opened = opened | s.mask;
+            let new_flow = flow + s.rate as u64;
+            let mut best_flow = flow + new_flow * (STEPS_PT1 - step) as u64;
+            let mut best_next = None;
+            for &(dest, dist) in &s.dests {
+                if self.is_opened(opened, dest) || step + dist > STEPS_PT1 {
+                    continue;
+                }
+                let next_flow = self.bfs(step + dist, new_flow, dest, opened, memo);
+                let next_flow = flow + new_flow * (dist - 1) as u64 + next_flow;
+                if next_flow >= best_flow {
+                    best_flow = next_flow;
+                    best_next = Some(dest);
+                }
             }
+            (best_flow, best_next)
+        });
+        best_flow
+    }
+
+    // Follow the choices recorded by `bfs` from the starting state. A bounded
+    // memo may have evicted some of them, those are searched again.
+    fn best_path(
+        &self,
+        memo: &mut Memo<SearchState, (u64, Option<StateId>), FxBuildHasher>,
+    ) -> Vec<(u8, StateId)> {
+        let mut path = Vec::new();
+        let mut current = SearchState {
+            opened: 0,
+            step: 0,
+            current: StateId(0),
+        };
+        let mut flow = 0;
+        loop {
+            path.push((current.step, current.current));
+            if memo.get(&current).is_none() {
+                self.bfs(current.step, flow, current.current, current.opened, memo);
+            }
+            let Some(&(_, Some(next))) = memo.get(&current) else {
+                break;
+            };
+            let s = &self.0[&current.current];
+            flow += s.rate as u64;
+            let &(_, dist) = s.dests.iter().find(|&&(id, _)| id == next).unwrap();
+            current = SearchState {
+                opened: current.opened | s.mask,
+                step: current.step + dist,
+                current: next,
+            };
         }
-        best_path.push((step, state));
-        memo.insert(search_state, (best_flow, best_path.clone()));
-        (best_flow, best_path)
+        path
     }
 
-    fn bfs2<'a, 'b: 'a>(
+    fn bfs2(
         &self,
         step: u8,
         flow: u64,
         states: [StateId; 2],
-        visited: Cow<'b, Vec<StateId>>,
-        memo: &'a mut FxHashMap<SearchState2<'b>, u64>,
+        opened: u64,
+        memo: &mut Memo<SearchState2, u64, FxBuildHasher>,
     ) -> u64 {
-        // dbg!(step);
-        // if step == 15 {
-        //     eprintln!("memo size: {}", memo.len());
-        // }
-        if visited.len() == self.1 {
-            // eprintln!("Skipping {} steps", 27 - step);
+        if opened.count_ones() as usize == self.1 {
             return flow * (27 - step as u64);
         }
-        let opened = visited.clone();
         let search_state = SearchState2 {
             current: states,
             step,
             opened,
         };
-        if let Some(flow) = memo.get(&search_state) {
-            return *flow;
-        }
-        if step >= 26 {
-            memo.insert(search_state, flow);
-            return flow;
-        }
-        let s1 = self.0.get(&states[0]).unwrap();
-        let s2 = self.0.get(&states[1]).unwrap();
-        let mut visited = visited.clone();
-        for s in states {
-            if s.is_open() {
-                visited.to_mut().push(s);
+        memo.get_or_insert_with(search_state, |memo| {
+            if step >= 26 {
+                return flow;
             }
-        }
+            let s1 = &self.0[&states[0]];
+            let s2 = &self.0[&states[1]];
+            let opened = opened | s1.mask | s2.mask;
 
-        let mut new_flow = flow + s1.rate as u64;
-        if states[0] != states[1] {
-            new_flow += s2.rate as u64;
-        }
-        let mut best_flow = flow;
-        for &(dest1, _) in &s1.dests {
-            if visited.contains(&dest1) {
-                continue;
+            let mut new_flow = flow + s1.rate as u64;
+            if states[0] != states[1] {
+                new_flow += s2.rate as u64;
             }
-            for &(dest2, _) in &s2.dests {
-                if visited.contains(&dest2) {
+            let mut best_flow = flow;
+            for &(dest1, _) in &s1.dests {
+                if self.is_opened(opened, dest1) {
                     continue;
                 }
-                let mut dest = [dest1, dest2];
-                dest.sort();
-                let next_flow = self.bfs2(step + 1, new_flow, dest, visited.clone(), memo);
-                if next_flow > best_flow {
-                    best_flow = next_flow;
+                for &(dest2, _) in &s2.dests {
+                    if self.is_opened(opened, dest2) {
+                        continue;
+                    }
+                    let mut dest = [dest1, dest2];
+                    dest.sort();
+                    let next_flow = self.bfs2(step + 1, new_flow, dest, opened, memo);
+                    if next_flow > best_flow {
+                        best_flow = next_flow;
+                    }
                 }
             }
+            best_flow + flow
+        })
+    }
+}
+
+/// Exploration commands, see `main`:
+/// - `memo [CAPACITY]`: answers and memo statistics of both searches, with the
+///   memo optionally bounded to `CAPACITY` entries
+/// - `path`: the valves visited alone, with the minute of each arrival
+pub fn explore(args: &[&str], input: impl Iterator<Item = String>) -> anyhow::Result<String> {
+    let capacity: Option<usize> = match args {
+        ["memo"] | ["path"] => None,
+        ["memo", capacity] => Some(capacity.parse()?),
+        _ => bail!("Expected `memo [CAPACITY]` or `path`"),
+    };
+    let mut g = Graph::default();
+    parse_lines(input, |line| g.add_line(line)).collect::<anyhow::Result<()>>()?;
+    let mut reduced = g.clone();
+    reduced.reduce();
+
+    if args == ["path"] {
+        let mut memo = Memo::new();
+        reduced.bfs(0, 0, StateId(0), 0, &mut memo);
+        let mut out = String::new();
+        for (step, valve) in reduced.best_path(&mut memo) {
+            writeln!(out, "minute {step}: {valve:?}")?;
         }
-        memo.insert(search_state, best_flow + flow);
-        best_flow + flow
+        return Ok(out);
     }
+
+    let mut memo = capacity.map_or_else(Memo::new, Memo::bounded);
+    let alone = reduced.bfs(0, 0, StateId(0), 0, &mut memo);
+    let mut out = format!("part 1: {alone} ({})\n", memo.stats());
+    let mut memo = capacity.map_or_else(Memo::new, Memo::bounded);
+    let together = g.bfs2(0, 0, [StateId(0), StateId(0)], 0, &mut memo);
+    writeln!(out, "part 2: {together} ({})", memo.stats())?;
+    Ok(out)
 }
 
 pub struct Part1;
@@ -281,15 +341,11 @@ impl Part for Part1 {
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
         let mut g = Graph::default();
-        input.for_each(|line| g.add_line(&line));
+        parse_lines(input, |line| g.add_line(line)).collect::<anyhow::Result<()>>()?;
         let mut g2 = g.clone();
         g2.reduce();
-        dbg!(&g2);
-        // dbg!(&g);
-        let mut memo = FxHashMap::default();
-        let (total, mut path) = g2.bfs(0, 0, StateId(0), Cow::Owned(Vec::new()), &mut memo);
-        path.reverse();
-        eprintln!("path: {path:?}");
+        let mut memo = Memo::new();
+        let total = g2.bfs(0, 0, StateId(0), 0, &mut memo);
         Ok(Num(total))
     }
 }
@@ -303,16 +359,68 @@ impl Part for Part2 {
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
         let mut g = Graph::default();
-        input.for_each(|line| g.add_line(&line));
-        // dbg!(&g);
-        let mut memo = FxHashMap::default();
-        let total = g.bfs2(
-            0,
-            0,
-            [StateId(0), StateId(0)],
-            Cow::Owned(Vec::new()),
-            &mut memo,
-        );
+        parse_lines(input, |line| g.add_line(line)).collect::<anyhow::Result<()>>()?;
+        let mut memo = Memo::new();
+        let total = g.bfs2(0, 0, [StateId(0), StateId(0)], 0, &mut memo);
         Ok(Num(total))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Graph {
+        let mut g = Graph::default();
+        for line in Day16::EXAMPLE.lines().filter(|l| !l.is_empty()) {
+            g.add_line(line).unwrap();
+        }
+        g.reduce();
+        g
+    }
+
+    #[test]
+    fn explore_reports_memo_stats() {
+        let input = || Day16::EXAMPLE.trim().lines().map(String::from);
+        let out = explore(&["memo"], input()).unwrap();
+        assert!(out.starts_with("part 1: 1651 ("), "{out}");
+        assert!(out.contains("\npart 2: 1707 ("), "{out}");
+        assert!(out.contains(" 0 evictions"), "{out}");
+        // part 2 needs about 100k entries, a much smaller memo is very slow
+        let out = explore(&["memo", "60000"], input()).unwrap();
+        let part2 = out.lines().nth(1).unwrap();
+        assert!(part2.starts_with("part 2: 1707 ("), "{out}");
+        assert!(!part2.ends_with(" 0 evictions)"), "{out}");
+    }
+
+    #[test]
+    fn explore_path() {
+        let input = Day16::EXAMPLE.trim().lines().map(String::from);
+        let out = explore(&["path"], input).unwrap();
+        assert!(out.starts_with("minute 0: AA\n"), "{out}");
+        // valves are opened at the same minutes as in the puzzle text
+        let opened: Vec<_> = out.lines().filter(|line| line.contains("open")).collect();
+        let expected = [
+            "minute 2: open DD",
+            "minute 5: open BB",
+            "minute 9: open JJ",
+            "minute 17: open HH",
+            "minute 21: open EE",
+            "minute 24: open CC",
+        ];
+        assert_eq!(opened, expected);
+    }
+
+    #[test]
+    fn best_path_survives_eviction() {
+        let g = example();
+        let mut memo = Memo::new();
+        assert_eq!(g.bfs(0, 0, StateId(0), 0, &mut memo), 1651);
+        let expected = g.best_path(&mut memo);
+
+        let mut bounded = Memo::bounded(8);
+        assert_eq!(g.bfs(0, 0, StateId(0), 0, &mut bounded), 1651);
+        assert!(bounded.stats().evictions > 0);
+        assert_eq!(g.best_path(&mut bounded), expected);
+    }
+}
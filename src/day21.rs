@@ -1,13 +1,17 @@
 use aoc_framework::*;
 
-use aoc_framework::anyhow::bail;
+use aoc_framework::anyhow::{bail, Context};
 
 use fxhash::{FxHashMap, FxHashSet};
 use itertools::Itertools;
 
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::fmt::{self, Debug, Formatter, Write};
 use std::str::FromStr;
 
+use crate::helpers::{Checked, Int};
+
 pub struct Day21;
 
 impl_day!(Day21::{Part1, Part2}: 2022[21], r"
@@ -34,7 +38,7 @@ struct Id(u32);
 impl Debug for Id {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
         for i in 0..4 {
-            let c = 'a' as u32 + (self.0 >> (8 * (3 - i))) & 0xff;
+            let c = 'a' as u32 + ((self.0 >> (8 * (3 - i))) & 0xff);
             f.write_char(char::from_u32(c).unwrap())?;
         }
         Ok(())
@@ -63,9 +67,20 @@ enum Operation {
 
 use Operation::*;
 
+impl Operation {
+    fn apply(self, lhs: &Int, rhs: &Int) -> anyhow::Result<Int> {
+        match self {
+            Add => lhs.try_add(rhs),
+            Sub => lhs.try_sub(rhs),
+            Mul => lhs.try_mul(rhs),
+            Div => lhs.try_div(rhs),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 enum Node {
-    Value(i64),
+    Value(Int),
     Op(Operation, [Id; 2]),
     Human,
 }
@@ -104,6 +119,14 @@ fn parse_expressions(input: impl Iterator<Item = String>) -> anyhow::Result<FxHa
     Ok(out)
 }
 
+// Values that do not fit in the framework's `Num` are reported as text
+fn answer(n: &Int) -> Answer {
+    match n.to_u64() {
+        Ok(n) => Num(n),
+        Err(_) => Str(Cow::Owned(n.to_string())),
+    }
+}
+
 pub struct Part1;
 
 impl Part for Part1 {
@@ -111,6 +134,7 @@ impl Part for Part1 {
     const N: u8 = 1;
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(152));
 
+    #[allow(clippy::clone_on_copy)]
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
         let mut exprs = parse_expressions(input)?;
         let root = "root".parse().unwrap();
@@ -120,63 +144,80 @@ impl Part for Part1 {
                 stack.pop();
                 continue;
             };
-            let Some((lhs, rhs)) = operands.iter().flat_map(|id| {
-                match exprs[id] {
-                    Value(v) => Some(v),
-                    _ => {stack.push(*id); None}
-                }
-            }).tuples().next() else {
+            let Some((lhs, rhs)) = operands
+                .iter()
+                .flat_map(|id| match &exprs[id] {
+                    Value(v) => Some(v.clone()),
+                    _ => {
+                        stack.push(*id);
+                        None
+                    }
+                })
+                .tuples()
+                .next()
+            else {
                 continue;
             };
             stack.pop();
-            let val = match op {
-                Add => lhs + rhs,
-                Sub => lhs - rhs,
-                Mul => lhs * rhs,
-                Div => lhs / rhs,
-            };
+            let val = op
+                .apply(&lhs, &rhs)
+                .with_context(|| format!("evaluating {id:?}"))?;
             exprs.insert(id, Value(val));
         }
-        let Value(rootval) = exprs[&root] else {
+        let Value(rootval) = &exprs[&root] else {
             bail!("Could not compute value of root");
         };
-        Ok(Num(rootval as u64))
+        Ok(answer(rootval))
     }
 }
 
 type Expressions = FxHashMap<Id, Node>;
 
-fn eval_pt2(exprs: &Expressions, new_exprs: &Expressions, root: Id, humn_val: i64) -> anyhow::Result<i64> {
+const MAX_HUMN_BITS: u32 = 4096;
+
+// `Int` is only `Copy` without the `bigint` feature
+#[allow(clippy::clone_on_copy)]
+fn eval_pt2(
+    exprs: &Expressions,
+    new_exprs: &Expressions,
+    root: Id,
+    humn_val: &Int,
+) -> anyhow::Result<Int> {
     let mut new_exprs = new_exprs.clone();
     let humn = "humn".parse().unwrap();
-    new_exprs.insert(humn, Value(humn_val));
+    new_exprs.insert(humn, Value(humn_val.clone()));
     let mut stack: Vec<Id> = vec![root];
     while let Some(id) = stack.last().copied() {
         let Op(op, operands) = new_exprs.get(&id).or_else(|| exprs.get(&id)).unwrap() else {
             stack.pop();
             continue;
         };
-        let Some((lhs, rhs)) = operands.iter().flat_map(|id| {
-            match new_exprs.get(id).or_else(|| exprs.get(id)).unwrap() {
-                Value(v) => Some(v),
-                _ => {stack.push(*id); None}
-            }
-        }).tuples().next() else {
+        let Some((lhs, rhs)) = operands
+            .iter()
+            .flat_map(
+                |id| match new_exprs.get(id).or_else(|| exprs.get(id)).unwrap() {
+                    Value(v) => Some(v.clone()),
+                    _ => {
+                        stack.push(*id);
+                        None
+                    }
+                },
+            )
+            .tuples()
+            .next()
+        else {
             continue;
         };
         stack.pop();
-        let val = match op {
-            Add => lhs + rhs,
-            Sub => lhs - rhs,
-            Mul => lhs * rhs,
-            Div => lhs / rhs,
-        };
+        let val = op
+            .apply(&lhs, &rhs)
+            .with_context(|| format!("evaluating {id:?} with humn = {humn_val}"))?;
         new_exprs.insert(id, Value(val));
     }
-    let Value(rootval) = new_exprs[&root] else {
+    let Value(rootval) = &new_exprs[&root] else {
         bail!("Could not compute value of root");
     };
-    Ok(rootval)
+    Ok(rootval.clone())
 }
 
 pub struct Part2;
@@ -186,6 +227,7 @@ impl Part for Part2 {
     const N: u8 = 2;
     const EXAMPLE_RESULT: Option<Answer> = Some(Num(301));
 
+    #[allow(clippy::clone_on_copy)]
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
         let mut exprs = parse_expressions(input)?;
         let root = "root".parse().unwrap();
@@ -198,70 +240,129 @@ impl Part for Part2 {
                 stack.pop();
                 continue;
             };
-            let Some((lhs, rhs)) = operands.iter().enumerate().flat_map(|(i, op_id)| {
-                if depend_on_humn.contains(op_id) {
-                    depend_on_humn.insert(id);
-                    for _ in 0..(i+1) {
-                    stack.pop();
-                    }
-                    return None;
-                }
-                match exprs[op_id] {
-                    Value(v) => Some(v),
-                    Human => {
+            let Some((lhs, rhs)) = operands
+                .iter()
+                .enumerate()
+                .flat_map(|(i, op_id)| {
+                    if depend_on_humn.contains(op_id) {
                         depend_on_humn.insert(id);
-                    for _ in 0..(i+1) {
-                    stack.pop();
+                        for _ in 0..(i + 1) {
+                            stack.pop();
+                        }
+                        return None;
                     }
-                        None
+                    match &exprs[op_id] {
+                        Value(v) => Some(v.clone()),
+                        Human => {
+                            depend_on_humn.insert(id);
+                            for _ in 0..(i + 1) {
+                                stack.pop();
+                            }
+                            None
+                        }
+                        _ => {
+                            stack.push(*op_id);
+                            None
+                        }
                     }
-                    _ => {stack.push(*op_id); None}
-                }
-            }).tuples().next() else {
+                })
+                .tuples()
+                .next()
+            else {
                 continue;
             };
             stack.pop();
-            let val = match op {
-                Add => lhs + rhs,
-                Sub => lhs - rhs,
-                Mul => lhs * rhs,
-                Div => lhs / rhs,
-            };
+            let val = op
+                .apply(&lhs, &rhs)
+                .with_context(|| format!("evaluating {id:?}"))?;
             exprs.insert(id, Value(val));
         }
         let Op(_, [lhs, rhs]) = exprs[&root] else {
             panic!()
         };
-        let (new_root, &target) = match (&exprs[&lhs], &exprs[&rhs]) {
+        let (new_root, target) = match (&exprs[&lhs], &exprs[&rhs]) {
             (Value(v), Op(_, _)) => (rhs, v),
             (Op(_, _), Value(v)) => (lhs, v),
             _ => bail!("Could not find target value"),
         };
+        let target = target.clone();
         let new_exprs: FxHashMap<Id, Node> = exprs
             .iter()
             .filter(|(id, _)| depend_on_humn.contains(id))
             .map(|(&id, node)| (id, node.clone()))
             .collect();
-        let eval = |v| eval_pt2(&exprs, &new_exprs, new_root, v);
-        let growing = eval(1000)? < eval(2000)?;
-        let mut range = 0..1_000_000_000_000_000;
-        loop {
-            let mut humn_val = range.start + (range.end - range.start) / 2;
-            let root_val = eval(humn_val)?;
-            if (root_val < target && growing) || (target < root_val && !growing) {
-                // eprintln!("{root_val} != {target} ({humn_val})");
-                range = (humn_val + 1)..range.end;
-            } else if (root_val > target && growing) || (target > root_val && !growing) {
-                // eprintln!("{root_val} != {target} ({humn_val})");
-                range = range.start..humn_val;
+        let eval = |v: &Int| eval_pt2(&exprs, &new_exprs, new_root, v);
+        let int = Int::from_i64;
+        let growing = eval(&int(1000)?)? < eval(&int(2000)?)?;
+        // `Less` while humn is too small
+        let side = |v: &Int| -> anyhow::Result<Ordering> {
+            let root_val = eval(v)?;
+            Ok(if growing {
+                root_val.cmp(&target)
             } else {
-                while eval(humn_val - 1)? == target {
-                    humn_val -= 1;
-                }
-                return Ok(Num(humn_val as u64));
+                target.cmp(&root_val)
+            })
+        };
+        // double the upper bound until it overshoots, then bisect for the
+        // smallest matching value
+        let (one, two) = (int(1)?, int(2)?);
+        let mut lo = int(0)?;
+        let mut hi = one.clone();
+        for doublings in 0.. {
+            if side(&hi)? != Ordering::Less {
+                break;
+            }
+            if doublings == MAX_HUMN_BITS {
+                bail!("No value of humn below 2^{MAX_HUMN_BITS} reaches {target}");
             }
+            lo = hi.try_add(&one)?;
+            hi = hi.try_mul(&two).context("searching for humn")?;
         }
-        bail!("Could not compute value of root");
-        Ok(Num(0))
+        while lo < hi {
+            let mid = lo.try_add(&hi.try_sub(&lo)?.try_div(&two)?)?;
+            if side(&mid)? == Ordering::Less {
+                lo = mid.try_add(&one)?;
+            } else {
+                hi = mid;
+            }
+        }
+        if side(&lo)? != Ordering::Equal {
+            bail!("No value of humn makes both sides of root equal");
+        }
+        Ok(answer(&lo))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // humn * 3 must equal 3e30, which needs more than 64 bits
+    const LARGE: &str = "root: aaaa + bbbb
+aaaa: humn * cccc
+cccc: 3
+bbbb: dddd * eeee
+dddd: 3000000000000000
+eeee: 1000000000000000
+humn: 0";
+
+    fn run_large() -> anyhow::Result<Answer> {
+        Part2::run(LARGE.lines().map(String::from))
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn large_values_report_overflow() {
+        let err = run_large().unwrap_err();
+        assert!(format!("{err:#}").contains("overflow"), "{err:#}");
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn large_values_are_solved() {
+        let Str(humn) = run_large().unwrap() else {
+            panic!("expected a text answer");
+        };
+        assert_eq!(humn, "1000000000000000000000000000000");
     }
 }
@@ -1,12 +1,7 @@
-use std::collections::VecDeque;
-
 use aoc_framework::*;
-use fxhash::{FxHashMap, FxHashSet};
-use itertools::Itertools;
-
-use crate::helpers::{Direction, Point};
+use std::fmt::Write;
 
-use Direction::*;
+use crate::helpers::BitGrid;
 
 pub struct Day23;
 
@@ -20,119 +15,126 @@ impl_day!(Day23::{Part1, Part2}: 2022[23], r"
 .#..#..
 ");
 
+#[derive(Clone, Copy)]
+enum Direction {
+    North,
+    South,
+    West,
+    East,
+}
+
+use Direction::*;
+
+impl Direction {
+    // cells of `BitGrid::neighbourhood` that must be free to move this way
+    fn checked(self) -> [usize; 3] {
+        match self {
+            North => [0, 1, 2],
+            South => [6, 7, 8],
+            West => [0, 3, 6],
+            East => [2, 5, 8],
+        }
+    }
+}
+
 struct State {
-    points: FxHashSet<Point>,
-    directions: VecDeque<Direction>,
-    intents: Vec<(Point, Point)>,
-    counts: FxHashMap<Point, u8>,
+    grid: BitGrid,
+    directions: [Direction; 4],
+    // one grid of elves proposing to move per direction
+    proposals: [BitGrid; 4],
 }
 
 impl State {
     fn simulate(&mut self) -> bool {
-        let mut moved = false;
-        self.intents.clear();
-        self.counts.clear();
-        // Avoid iterating on VecDeque in hot loop
-        let directions: [Direction; 4] = [
-            self.directions[0],
-            self.directions[1],
-            self.directions[2],
-            self.directions[3],
-        ];
-        for &p in self.points.iter() {
-            let mut neighbors = [false; 3 * 3];
-            let mut count = 0;
-            neighbors.iter_mut().enumerate().for_each(|(i, b)| {
-                *b = i != 4
-                    && self.points.contains(&Point {
-                        x: p.x + (i % 3 - 1) as isize,
-                        y: p.y + (i / 3 - 1) as isize,
-                    });
-                if *b {
-                    count += 1;
+        self.grid.reserve_margin();
+        for proposals in &mut self.proposals {
+            *proposals = self.grid.empty_like();
+        }
+        let (w, h) = (self.grid.width(), self.grid.height());
+        for row in 0..h {
+            for word in 0..w {
+                let neighbours = self.grid.neighbourhood(row, word);
+                let elves = neighbours[4];
+                if elves == 0 {
+                    continue;
+                }
+                let crowded = neighbours
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != 4)
+                    .fold(0, |acc, (_, &mask)| acc | mask);
+                // elves without neighbours do not move
+                let mut undecided = elves & crowded;
+                for dir in self.directions {
+                    let free = !dir.checked().iter().fold(0, |acc, &i| acc | neighbours[i]);
+                    self.proposals[dir as usize].set_word(row, word, undecided & free);
+                    undecided &= !free;
                 }
-            });
-            if count == 0 {
-                // no neighbors, no move
-                continue;
-            }
-            let intent = directions
-                .into_iter()
-                .find(|&dir| {
-                    ((dir as i8 - 1)..=(dir as i8 + 1))
-                        .map(|d| (dir.delta() | Direction::from(d).delta()) + Point { x: 1, y: 1 })
-                        .all(|dest| !neighbors[dest.offset(3)])
-                })
-                .map(|dir| p + dir.delta());
-            if let Some(dest) = intent {
-                self.intents.push((p, dest));
-                *self.counts.entry(dest).or_default() += 1;
             }
         }
-        for &(p, dest) in &self.intents {
-            if self.counts[&dest] > 1 {
-                continue;
+
+        let [north, south, west, east] = &self.proposals;
+        let mut next = self.grid.empty_like();
+        let mut moved = false;
+        for row in 0..h {
+            let y = row as isize;
+            for word in 0..w {
+                // elves arriving on each cell, by direction of travel
+                let from_south = north.shifted(y + 1, word, 0);
+                let from_north = south.shifted(y - 1, word, 0);
+                let from_east = west.shifted(y, word, 1);
+                let from_west = east.shifted(y, word, -1);
+                // only elves moving in opposite directions can collide
+                let vertical = (from_south | from_north) & !(from_south & from_north);
+                let horizontal = (from_east | from_west) & !(from_east & from_west);
+                let arrived = vertical | horizontal;
+
+                let blocked = (north.word(row, word) & south.shifted(y - 2, word, 0))
+                    | (south.word(row, word) & north.shifted(y + 2, word, 0))
+                    | (west.word(row, word) & east.shifted(y, word, -2))
+                    | (east.word(row, word) & west.shifted(y, word, 2));
+                let proposed = north.word(row, word)
+                    | south.word(row, word)
+                    | west.word(row, word)
+                    | east.word(row, word);
+                let stayed = (self.grid.word(row, word) & !proposed) | blocked;
+                next.set_word(row, word, stayed | arrived);
+                moved |= arrived != 0;
             }
-            self.points.remove(&p);
-            self.points.insert(dest);
-            moved = true;
         }
-        // self.display();
+        self.grid = next;
         self.directions.rotate_left(1);
         moved
     }
 
     fn new(input: impl Iterator<Item = String>) -> Self {
-        let mut points: FxHashSet<Point> = FxHashSet::default();
+        let mut grid = BitGrid::new();
         input.enumerate().for_each(|(y, ln)| {
             ln.bytes()
                 .enumerate()
                 .filter(|(_, b)| *b == b'#')
-                .for_each(|(x, _)| {
-                    points.insert((x, y).into());
-                })
+                .for_each(|(x, _)| grid.insert(x as isize, y as isize))
         });
-        let mut directions = VecDeque::with_capacity(4);
-        directions.push_back(Up);
-        directions.push_back(Down);
-        directions.push_back(Left);
-        directions.push_back(Right);
-        let intents = Vec::with_capacity(points.len());
-        let mut counts = FxHashMap::default();
-        counts.reserve(points.len());
         State {
-            points,
-            directions,
-            intents,
-            counts,
+            grid,
+            directions: [North, South, West, East],
+            proposals: Default::default(),
         }
     }
+}
 
-    fn display(&self) {
-        let mut minx = isize::MAX;
-        let mut maxx = isize::MIN;
-        let mut miny = isize::MAX;
-        let mut maxy = isize::MIN;
-        self.points.iter().for_each(|&Point { x, y }| {
-            minx = minx.min(x);
-            maxx = maxx.max(x);
-            miny = miny.min(y);
-            maxy = maxy.max(y);
-        });
-
-        miny = miny.min(-1);
-        minx = minx.min(-3);
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some((minx, miny, maxx, maxy)) = self.grid.bounds() else {
+            return Ok(());
+        };
         for y in miny..=maxy {
             for x in minx..=maxx {
-                if self.points.contains(&Point { x, y }) {
-                    eprint!("#");
-                } else {
-                    eprint!(".");
-                }
+                f.write_char(if self.grid.contains(x, y) { '#' } else { '.' })?;
             }
-            eprintln!();
+            f.write_char('\n')?;
         }
-        eprintln!();
+        Ok(())
     }
 }
 
@@ -149,17 +151,10 @@ impl Part for Part1 {
         for _ in 0..10 {
             state.simulate();
         }
-        let mut minx = isize::MAX;
-        let mut maxx = isize::MIN;
-        let mut miny = isize::MAX;
-        let mut maxy = isize::MIN;
-        state.points.iter().for_each(|&Point { x, y }| {
-            minx = minx.min(x);
-            maxx = maxx.max(x);
-            miny = miny.min(y);
-            maxy = maxy.max(y);
-        });
-        let total = ((maxx - minx + 1) * (maxy - miny + 1)) as usize - state.points.len();
+        let Some((minx, miny, maxx, maxy)) = state.grid.bounds() else {
+            return Ok(Num(0));
+        };
+        let total = ((maxx - minx + 1) * (maxy - miny + 1)) as usize - state.grid.len();
         Ok(Num(total as u64))
     }
 }
@@ -178,3 +173,27 @@ impl Part for Part2 {
         Ok(Num(res))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_example_rounds() {
+        let mut state = State::new(
+            [".....", "..##.", "..#..", ".....", "..##.", "....."]
+                .map(String::from)
+                .into_iter(),
+        );
+        let rounds = [
+            "##\n..\n#.\n.#\n#.\n",
+            ".##.\n#...\n...#\n....\n.#..\n",
+            "..#..\n....#\n#....\n....#\n.....\n..#..\n",
+        ];
+        for expected in rounds {
+            assert!(state.simulate());
+            assert_eq!(state.to_string(), expected);
+        }
+        assert!(!state.simulate());
+    }
+}
@@ -1,6 +1,11 @@
 use std::borrow::Cow;
 
-use aoc_framework::*;
+use aoc_framework::{
+    anyhow::{bail, Context},
+    *,
+};
+
+use crate::helpers::{parse_lines, Checked, Int};
 
 pub struct Day25;
 
@@ -20,28 +25,29 @@ impl_day!(Day25::{Part1, Part2}: 2022[25], r"
 122
 ");
 
-fn parse_num(s: &str) -> i64 {
-    s.bytes().fold(0, |acc, b| {
-        acc * 5
-            + match b {
-                b'=' => -2,
-                b'-' => -1,
-                b'0'..=b'2' => (b - b'0') as i64,
-                _ => panic!("Invalid input"),
-            }
+fn parse_num(s: &str) -> anyhow::Result<Int> {
+    let base = Int::from_i64(5)?;
+    s.bytes().try_fold(Int::from_i64(0)?, |acc, b| {
+        let digit = match b {
+            b'=' => -2,
+            b'-' => -1,
+            b'0'..=b'2' => (b - b'0') as i64,
+            _ => bail!("Invalid digit {:?}", b as char),
+        };
+        acc.try_mul(&base)?.try_add(&Int::from_i64(digit)?)
     })
 }
 
-fn convert(mut n: i64, out: &mut String) {
-    if n == 0 {
-        return;
-    }
-    let d = n % 5;
-    if d > 2 {
-        n += 5;
+fn convert(mut n: Int, out: &mut String) -> anyhow::Result<()> {
+    if n.is_zero() {
+        return Ok(());
     }
-    n /= 5;
-    convert(n, out);
+    let base = Int::from_i64(5)?;
+    // digits 3 and 4 are written as -2 and -1, carrying one to the next digit
+    let d = n.try_rem_euclid(&base)?.to_u64()?;
+    let digit = Int::from_i64(if d > 2 { d as i64 - 5 } else { d as i64 })?;
+    n = n.try_sub(&digit)?.try_div(&base)?;
+    convert(n, out)?;
     out.push(match d {
         0 => '0',
         1 => '1',
@@ -50,6 +56,7 @@ fn convert(mut n: i64, out: &mut String) {
         4 => '-',
         _ => unreachable!(),
     });
+    Ok(())
 }
 
 pub struct Part1;
@@ -60,9 +67,14 @@ impl Part for Part1 {
     const EXAMPLE_RESULT: Option<Answer> = Some(ConstStr("2=-1=0"));
 
     fn run(input: impl Iterator<Item = String>) -> anyhow::Result<Answer> {
-        let total = input.map(|s| parse_num(&s)).sum();
+        let total = parse_lines(input, parse_num).try_fold(Int::from_i64(0)?, |acc, n| {
+            acc.try_add(&n?).context("Overflow computing the total")
+        })?;
         let mut out = String::new();
-        convert(total, &mut out);
+        convert(total, &mut out)?;
+        if out.is_empty() {
+            out.push('0');
+        }
         Ok(Str(Cow::Owned(out)))
     }
 }
@@ -78,3 +90,40 @@ impl Part for Part2 {
         Ok(Num(0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snafu(n: i64) -> String {
+        let mut out = String::new();
+        convert(Int::from_i64(n).unwrap(), &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn negative_numbers() {
+        assert_eq!(snafu(-1), "-");
+        assert_eq!(snafu(-3), "-2");
+        assert_eq!(snafu(-4), "-1");
+        assert_eq!(snafu(-10), "=0");
+        // negating flips the sign of every digit of 4890, "2=-1=0"
+        assert_eq!(snafu(-4890), "=21-20");
+        for n in -3000..3000 {
+            assert_eq!(
+                parse_num(&snafu(n)).unwrap(),
+                Int::from_i64(n).unwrap(),
+                "{n}"
+            );
+        }
+        let total = |lines: &[&str]| {
+            let input = lines.iter().map(|line| line.to_string());
+            let Str(total) = Part1::run(input).unwrap() else {
+                panic!("expected a string");
+            };
+            total.into_owned()
+        };
+        assert_eq!(total(&["1=", "-", "=", "="]), "=");
+        assert_eq!(total(&["1=", "-", "="]), "0");
+    }
+}
@@ -1,11 +1,220 @@
-use std::fmt::Debug;
 use std::str::FromStr;
 
-pub fn parse_tuple<L: FromStr, R: FromStr>(s: &str, pat: char) -> (L, R)
+use aoc_framework::anyhow::{self, anyhow, bail, Context};
+
+mod bitgrid;
+mod charset;
+mod int;
+mod memo;
+mod tileset;
+
+pub use bitgrid::BitGrid;
+pub use charset::{Alphabet, CharSet};
+pub use int::{Checked, Int, UInt};
+pub use memo::Memo;
+pub use tileset::TileSet;
+
+pub fn parse_tuple<L: FromStr, R: FromStr>(s: &str, pat: char) -> anyhow::Result<(L, R)>
+where
+    L::Err: Into<anyhow::Error>,
+    R::Err: Into<anyhow::Error>,
+{
+    let (l, r) = s
+        .split_once(pat)
+        .ok_or_else(|| anyhow!("Expected {pat:?} in {s:?}"))?;
+    Ok((parse_field(l)?, parse_field(r)?))
+}
+
+fn parse_field<T: FromStr>(s: &str) -> anyhow::Result<T>
+where
+    T::Err: Into<anyhow::Error>,
+{
+    s.parse::<T>()
+        .map_err(Into::<anyhow::Error>::into)
+        .with_context(|| format!("Invalid value {s:?}"))
+}
+
+/// Matches `s` against a template in which every `{}` captures a field, e.g.
+/// `"move {} from {} to {}"`, and returns the captured fields in order.
+///
+/// A capture extends up to the next occurrence of the literal text following
+/// it, or to the end of the line for a trailing `{}`.
+pub fn scan<'a, const N: usize>(pattern: &str, s: &'a str) -> anyhow::Result<[&'a str; N]> {
+    let mut literals = pattern.split("{}");
+    let prefix = literals.next().unwrap_or_default();
+    let mut rest = s
+        .strip_prefix(prefix)
+        .ok_or_else(|| anyhow!("Expected {prefix:?} at the start of {s:?}"))?;
+    let mut fields = [""; N];
+    let mut n = 0;
+    for literal in literals {
+        if n == N {
+            bail!("Pattern {pattern:?} has more than {N} fields");
+        }
+        let end = if literal.is_empty() {
+            if rest.is_empty() {
+                bail!("Missing field {} in {s:?}", n + 1);
+            }
+            rest.len()
+        } else {
+            rest.find(literal)
+                .ok_or_else(|| anyhow!("Expected {literal:?} after field {} in {s:?}", n + 1))?
+        };
+        fields[n] = &rest[..end];
+        rest = &rest[end + literal.len()..];
+        n += 1;
+    }
+    if n != N {
+        bail!("Pattern {pattern:?} has {n} fields, expected {N}");
+    }
+    if !rest.is_empty() {
+        bail!("Unexpected trailing input {rest:?} in {s:?}");
+    }
+    Ok(fields)
+}
+
+/// Like [`scan`], but parses every captured field as a `T`.
+pub fn scan_parse<T: FromStr, const N: usize>(pattern: &str, s: &str) -> anyhow::Result<[T; N]>
+where
+    T::Err: Into<anyhow::Error>,
+{
+    let parsed = scan::<N>(pattern, s)?
+        .into_iter()
+        .map(parse_field)
+        .collect::<anyhow::Result<Vec<T>>>()?;
+    parsed
+        .try_into()
+        .map_err(|_| anyhow!("Expected {N} fields in {s:?}"))
+}
+
+/// Extracts every (optionally negative) integer from arbitrary text.
+pub fn ints<T: FromStr>(s: &str) -> impl Iterator<Item = anyhow::Result<T>> + '_
 where
-    L::Err: Debug,
-    R::Err: Debug,
+    T::Err: Into<anyhow::Error>,
 {
-    let (l, r) = s.split_once(pat).unwrap();
-    (l.parse().unwrap(), r.parse().unwrap())
+    let bytes = s.as_bytes();
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let start = pos + bytes[pos..].iter().position(u8::is_ascii_digit)?;
+        let len = bytes[start..]
+            .iter()
+            .position(|b| !b.is_ascii_digit())
+            .unwrap_or(bytes.len() - start);
+        pos = start + len;
+        // only treat '-' as a sign if it is not used as a separator, e.g. "2-4"
+        let signed = start > 0
+            && bytes[start - 1] == b'-'
+            && (start < 2 || !bytes[start - 2].is_ascii_digit());
+        let start = if signed { start - 1 } else { start };
+        Some(parse_field(&s[start..pos]))
+    })
+}
+
+/// Parses every line with `f`, adding the line number and content to errors.
+pub fn parse_lines<T>(
+    input: impl Iterator<Item = String>,
+    mut f: impl FnMut(&str) -> anyhow::Result<T>,
+) -> impl Iterator<Item = anyhow::Result<T>> {
+    input
+        .enumerate()
+        .map(move |(i, line)| f(&line).with_context(|| format!("line {}: {line:?}", i + 1)))
+}
+
+/// Splits the input into blocks of lines separated by blank lines.
+pub struct Blocks<I> {
+    input: I,
+}
+
+impl<I: Iterator<Item = String>> Iterator for Blocks<I> {
+    type Item = Vec<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut block = Vec::new();
+        for line in self.input.by_ref() {
+            if !line.is_empty() {
+                block.push(line);
+            } else if !block.is_empty() {
+                break;
+            }
+        }
+        (!block.is_empty()).then_some(block)
+    }
+}
+
+pub fn blocks<I: Iterator<Item = String>>(input: I) -> Blocks<I> {
+    Blocks { input }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(s: &str) -> impl Iterator<Item = String> + '_ {
+        s.lines().map(String::from)
+    }
+
+    #[test]
+    fn scan_fields() {
+        let fields = scan::<3>("move {} from {} to {}", "move 1 from 2 to 3").unwrap();
+        assert_eq!(fields, ["1", "2", "3"]);
+        assert_eq!(scan::<2>("{}-{}", "a-b-c").unwrap(), ["a", "b-c"]);
+        assert_eq!(scan::<1>("[{}]", "[]").unwrap(), [""]);
+        let err = |pattern, s| scan::<2>(pattern, s).unwrap_err().to_string();
+        assert!(err("x={}, y={}", "y=1, x=2").contains("at the start"));
+        assert!(err("{}, {}", "1 2").contains("after field 1"));
+        assert!(err("{} {}", "1 ").contains("Missing field 2"));
+        assert!(err("{}-{})", "1-2) ").contains("trailing input"));
+        assert!(err("{}", "1").contains("has 1 fields, expected 2"));
+        assert!(scan::<1>("{} {}", "1 2").is_err());
+    }
+
+    #[test]
+    fn scan_parse_fields() {
+        let [x, y] = scan_parse::<i64, 2>("x={}, y={}", "x=-3, y=14").unwrap();
+        assert_eq!((x, y), (-3, 14));
+        let err = scan_parse::<u8, 2>("{},{}", "1,300").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid value \"300\"");
+        assert!(scan_parse::<u8, 2>("{},{}", "1;2").is_err());
+    }
+
+    #[test]
+    fn signed_ints() {
+        let parse = |s| ints::<i64>(s).collect::<anyhow::Result<Vec<_>>>().unwrap();
+        assert_eq!(parse("x=-3, y=14"), [-3, 14]);
+        assert_eq!(parse("2-4,-6--8"), [2, 4, -6, -8]);
+        assert_eq!(parse("-1"), [-1]);
+        assert_eq!(parse("no numbers - here"), []);
+        let unsigned: Vec<_> = ints::<u32>("1 -2").collect();
+        assert_eq!(unsigned[0].as_ref().unwrap(), &1);
+        assert!(unsigned[1].is_err());
+        assert!(ints::<u8>("256").next().unwrap().is_err());
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let parsed = parse_lines(lines("1\n2\nthree\n4"), |line| Ok(line.parse::<u32>()?))
+            .collect::<Vec<_>>();
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(*parsed[1].as_ref().unwrap(), 2);
+        let err = parsed[2].as_ref().unwrap_err();
+        assert_eq!(err.to_string(), "line 3: \"three\"");
+        assert_eq!(
+            format!("{err:#}"),
+            "line 3: \"three\": invalid digit found in string"
+        );
+        let err = parse_lines(lines("a-b\nx"), |line| {
+            parse_tuple::<String, String>(line, '-')
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 2: \"x\": Expected '-' in \"x\"");
+    }
+
+    #[test]
+    fn blank_lines_separate_blocks() {
+        let found: Vec<_> = blocks(lines("\n\na\nb\n\n\n\nc\n\n")).collect();
+        assert_eq!(found, [vec!["a", "b"], vec!["c"]]);
+        assert_eq!(blocks(lines("\n\n")).count(), 0);
+        assert_eq!(blocks(lines("a")).collect::<Vec<_>>(), [vec!["a"]]);
+    }
 }
@@ -0,0 +1,247 @@
+/// Set of cells stored as rows of 64-bit words, for dense-ish cellular automata.
+///
+/// The grid grows in any direction as cells are inserted. Bit `i` of word `w`
+/// in a row stands for the cell at `x = x0 + 64 * w + i`. All grids created
+/// with [`BitGrid::empty_like`] share the same geometry, so their words can be
+/// combined directly.
+#[derive(Clone, Default, Debug)]
+pub struct BitGrid {
+    data: Vec<u64>,
+    width: usize,
+    height: usize,
+    x0: isize,
+    y0: isize,
+}
+
+const GROW_ROWS: usize = 8;
+
+impl BitGrid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn empty_like(&self) -> Self {
+        BitGrid {
+            data: vec![0; self.data.len()],
+            ..*self
+        }
+    }
+
+    /// Width of each row, in words
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn locate(&self, x: isize, y: isize) -> Option<(usize, usize, u32)> {
+        let (dx, row) = (x - self.x0, y - self.y0);
+        if dx < 0 || row < 0 || dx as usize >= self.width * 64 || row as usize >= self.height {
+            return None;
+        }
+        let dx = dx as usize;
+        Some((row as usize, dx / 64, (dx % 64) as u32))
+    }
+
+    pub fn contains(&self, x: isize, y: isize) -> bool {
+        self.locate(x, y)
+            .map(|(row, word, bit)| self.data[row * self.width + word] & (1 << bit) != 0)
+            .unwrap_or(false)
+    }
+
+    pub fn insert(&mut self, x: isize, y: isize) {
+        if self.width == 0 {
+            self.x0 = x.div_euclid(64) * 64;
+            self.y0 = y;
+            self.width = 1;
+            self.height = 1;
+            self.data = vec![0];
+        }
+        let left = (self.x0 - x).max(0) as usize;
+        let right = (x - (self.x0 + self.width as isize * 64 - 1)).max(0) as usize;
+        let top = (self.y0 - y).max(0) as usize;
+        let bottom = (y - (self.y0 + self.height as isize - 1)).max(0) as usize;
+        if left + right + top + bottom > 0 {
+            // grow by at least half the current size on each side that needs it,
+            // so that inserting along a line costs amortized O(1)
+            let at_least = |needed: usize, size: usize| match needed {
+                0 => 0,
+                _ => needed.max(size / 2),
+            };
+            self.grow(
+                at_least(top, self.height),
+                at_least(bottom, self.height),
+                at_least(left.div_ceil(64), self.width),
+                at_least(right.div_ceil(64), self.width),
+            );
+        }
+        let (row, word, bit) = self.locate(x, y).unwrap();
+        self.data[row * self.width + word] |= 1 << bit;
+    }
+
+    fn grow(&mut self, top: usize, bottom: usize, left: usize, right: usize) {
+        let width = self.width + left + right;
+        let height = self.height + top + bottom;
+        let mut data = vec![0; width * height];
+        for (row, words) in self.data.chunks_exact(self.width).enumerate() {
+            let start = (row + top) * width + left;
+            data[start..start + self.width].copy_from_slice(words);
+        }
+        self.data = data;
+        self.width = width;
+        self.height = height;
+        self.x0 -= left as isize * 64;
+        self.y0 -= top as isize;
+    }
+
+    /// Makes sure every set cell has its 8 neighbours inside the grid.
+    pub fn reserve_margin(&mut self) {
+        if self.width == 0 {
+            return;
+        }
+        let first_row = &self.data[..self.width];
+        let last_row = &self.data[self.data.len() - self.width..];
+        let top = if first_row.iter().any(|&w| w != 0) {
+            GROW_ROWS
+        } else {
+            0
+        };
+        let bottom = if last_row.iter().any(|&w| w != 0) {
+            GROW_ROWS
+        } else {
+            0
+        };
+        let rows = self.data.chunks_exact(self.width);
+        let left = rows.clone().any(|words| words[0] & 1 != 0) as usize;
+        let right = rows.clone().any(|words| words[self.width - 1] >> 63 != 0) as usize;
+        if top + bottom + left + right > 0 {
+            self.grow(top, bottom, left, right);
+        }
+    }
+
+    pub fn word(&self, row: usize, word: usize) -> u64 {
+        self.data[row * self.width + word]
+    }
+
+    pub fn set_word(&mut self, row: usize, word: usize, value: u64) {
+        self.data[row * self.width + word] = value;
+    }
+
+    /// Returns the word whose bit `i` holds the cell `dx` columns to the right
+    /// of the cell that bit `i` of `word` in `row` stands for. Cells outside
+    /// of the grid are empty.
+    pub fn shifted(&self, row: isize, word: usize, dx: i32) -> u64 {
+        if row < 0 || row as usize >= self.height {
+            return 0;
+        }
+        let words = &self.data[row as usize * self.width..][..self.width];
+        let cur = words[word];
+        match dx {
+            0 => cur,
+            1..=63 => {
+                let next = words.get(word + 1).copied().unwrap_or(0);
+                (cur >> dx) | (next << (64 - dx))
+            }
+            -63..=-1 => {
+                let prev = word.checked_sub(1).map(|w| words[w]).unwrap_or(0);
+                (cur << -dx) | (prev >> (64 + dx))
+            }
+            _ => panic!("shift out of range: {dx}"),
+        }
+    }
+
+    /// Word-parallel 3x3 neighbourhood of the cells in `word` of `row`, in
+    /// reading order: `[nw, n, ne, w, self, e, sw, s, se]`.
+    pub fn neighbourhood(&self, row: usize, word: usize) -> [u64; 9] {
+        let mut out = [0; 9];
+        for (i, mask) in out.iter_mut().enumerate() {
+            let dy = i as isize / 3 - 1;
+            let dx = i as i32 % 3 - 1;
+            *mask = self.shifted(row as isize + dy, word, dx);
+        }
+        out
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Smallest `(min_x, min_y, max_x, max_y)` rectangle containing every set cell
+    pub fn bounds(&self) -> Option<(isize, isize, isize, isize)> {
+        if self.width == 0 {
+            return None;
+        }
+        let rows = || {
+            self.data
+                .chunks_exact(self.width)
+                .enumerate()
+                .filter(|(_, words)| words.iter().any(|&w| w != 0))
+        };
+        let (min_row, _) = rows().next()?;
+        let (max_row, _) = rows().next_back()?;
+        let (min_x, max_x) = rows().fold((usize::MAX, 0), |(min_x, max_x), (_, words)| {
+            let first = words.iter().position(|&w| w != 0).unwrap();
+            let last = words.iter().rposition(|&w| w != 0).unwrap();
+            (
+                min_x.min(first * 64 + words[first].trailing_zeros() as usize),
+                max_x.max(last * 64 + 63 - words[last].leading_zeros() as usize),
+            )
+        });
+        Some((
+            self.x0 + min_x as isize,
+            self.y0 + min_row as isize,
+            self.x0 + max_x as isize,
+            self.y0 + max_row as isize,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_grid_has_no_bounds() {
+        assert_eq!(BitGrid::new().bounds(), None);
+        let mut grid = BitGrid::new();
+        grid.insert(3, 4);
+        assert_eq!(grid.empty_like().bounds(), None);
+    }
+
+    #[test]
+    fn grows_in_every_direction() {
+        let cells = [(0, 0), (-70, 5), (130, -9), (64, 64), (-1, -1)];
+        let mut grid = BitGrid::new();
+        for &(x, y) in &cells {
+            grid.insert(x, y);
+        }
+        for &(x, y) in &cells {
+            assert!(grid.contains(x, y), "missing ({x}, {y})");
+        }
+        assert!(!grid.contains(1, 0));
+        assert!(!grid.contains(-1000, 0));
+        assert_eq!(grid.len(), cells.len());
+        assert_eq!(grid.bounds(), Some((-70, -9, 130, 64)));
+    }
+
+    #[test]
+    fn neighbourhood_crosses_word_boundaries() {
+        let mut grid = BitGrid::new();
+        // x = 63 and x = 64 sit in different words
+        grid.insert(63, 0);
+        grid.insert(64, 1);
+        grid.reserve_margin();
+        let locate = |x: isize, y: isize| grid.locate(x, y).unwrap();
+        let (row, word, bit) = locate(64, 1);
+        // the cell at (64, 1) sees (63, 0) to its north-west
+        let [nw, n, ..] = grid.neighbourhood(row, word);
+        assert_ne!(nw & (1 << bit), 0);
+        assert_eq!(n & (1 << bit), 0);
+        // and (63, 0) sees (64, 1) to its south-east
+        let (row, word, bit) = locate(63, 0);
+        let [.., se] = grid.neighbourhood(row, word);
+        assert_ne!(se & (1 << bit), 0);
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, Sub};
+use std::str::FromStr;
+
+use aoc_framework::anyhow::{self, anyhow, bail};
+
+/// Characters a [`CharSet`] can hold, in priority order.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Alphabet {
+    /// `a..=z`
+    Lowercase,
+    /// `a..=z` followed by `A..=Z`
+    MixedCase,
+    /// Any ASCII character
+    Ascii,
+    /// Any byte
+    Bytes,
+}
+
+impl Alphabet {
+    pub fn len(self) -> usize {
+        match self {
+            Alphabet::Lowercase => 26,
+            Alphabet::MixedCase => 52,
+            Alphabet::Ascii => 128,
+            Alphabet::Bytes => 256,
+        }
+    }
+
+    pub fn index(self, b: u8) -> Option<usize> {
+        let ndx = match (self, b) {
+            (Alphabet::Lowercase | Alphabet::MixedCase, b'a'..=b'z') => b - b'a',
+            (Alphabet::MixedCase, b'A'..=b'Z') => b - b'A' + 26,
+            (Alphabet::Ascii, 0..=127) | (Alphabet::Bytes, _) => b,
+            _ => return None,
+        };
+        Some(ndx as usize)
+    }
+
+    pub fn byte(self, index: usize) -> u8 {
+        debug_assert!(index < self.len());
+        match self {
+            Alphabet::Lowercase | Alphabet::MixedCase if index < 26 => b'a' + index as u8,
+            Alphabet::MixedCase => b'A' + (index - 26) as u8,
+            _ => index as u8,
+        }
+    }
+
+    /// 1-based position of `b` in the alphabet
+    pub fn priority(self, b: u8) -> Option<u64> {
+        self.index(b).map(|ndx| ndx as u64 + 1)
+    }
+}
+
+impl FromStr for Alphabet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "lowercase" => Alphabet::Lowercase,
+            "mixed" => Alphabet::MixedCase,
+            "ascii" => Alphabet::Ascii,
+            "bytes" => Alphabet::Bytes,
+            _ => bail!("Unknown alphabet {s:?}, expected lowercase, mixed, ascii or bytes"),
+        })
+    }
+}
+
+/// Set of characters from an [`Alphabet`], stored as a bitmask.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CharSet {
+    bits: [u64; 4],
+    alphabet: Alphabet,
+}
+
+impl CharSet {
+    pub fn new(alphabet: Alphabet) -> Self {
+        CharSet {
+            bits: [0; 4],
+            alphabet,
+        }
+    }
+
+    /// Set containing every character of the alphabet
+    pub fn full(alphabet: Alphabet) -> Self {
+        let mut set = Self::new(alphabet);
+        for ndx in 0..alphabet.len() {
+            set.bits[ndx / 64] |= 1 << (ndx % 64);
+        }
+        set
+    }
+
+    pub fn from_bytes(alphabet: Alphabet, bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut set = Self::new(alphabet);
+        for &b in bytes {
+            set.insert(b)?;
+        }
+        Ok(set)
+    }
+
+    /// Adds `b` to the set, returning whether it was already present
+    pub fn insert(&mut self, b: u8) -> anyhow::Result<bool> {
+        let ndx = self.alphabet.index(b).ok_or_else(|| {
+            anyhow!(
+                "Invalid character {:?} for alphabet {:?}",
+                b as char,
+                self.alphabet
+            )
+        })?;
+        let mask = 1 << (ndx % 64);
+        let present = self.bits[ndx / 64] & mask != 0;
+        self.bits[ndx / 64] |= mask;
+        Ok(present)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Characters in the set, in alphabet order
+    pub fn iter(&self) -> impl Iterator<Item = u8> {
+        let CharSet { bits, alphabet } = *self;
+        bits.into_iter().enumerate().flat_map(move |(w, mut word)| {
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(alphabet.byte(w * 64 + bit))
+            })
+        })
+    }
+
+    /// Priorities of the characters in the set, in increasing order
+    pub fn priorities(&self) -> impl Iterator<Item = u64> {
+        let alphabet = self.alphabet;
+        self.iter().flat_map(move |b| alphabet.priority(b))
+    }
+
+    fn combine(self, rhs: Self, f: impl Fn(u64, u64) -> u64) -> Self {
+        debug_assert_eq!(self.alphabet, rhs.alphabet);
+        let mut bits = self.bits;
+        bits.iter_mut()
+            .zip(rhs.bits)
+            .for_each(|(l, r)| *l = f(*l, r));
+        CharSet { bits, ..self }
+    }
+}
+
+impl fmt::Display for CharSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.iter()
+            .try_for_each(|b| fmt::Write::write_char(f, b as char))
+    }
+}
+
+impl BitAnd for CharSet {
+    type Output = CharSet;
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.combine(rhs, |l, r| l & r)
+    }
+}
+
+impl BitOr for CharSet {
+    type Output = CharSet;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.combine(rhs, |l, r| l | r)
+    }
+}
+
+impl Sub for CharSet {
+    type Output = CharSet;
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.combine(rhs, |l, r| l & !r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABETS: [Alphabet; 4] = [
+        Alphabet::Lowercase,
+        Alphabet::MixedCase,
+        Alphabet::Ascii,
+        Alphabet::Bytes,
+    ];
+
+    #[test]
+    fn index_and_byte_round_trip() {
+        for alphabet in ALPHABETS {
+            for ndx in 0..alphabet.len() {
+                let b = alphabet.byte(ndx);
+                assert_eq!(alphabet.index(b), Some(ndx), "{alphabet:?} {b}");
+            }
+            let accepted = (0..=255).filter(|&b| alphabet.index(b).is_some()).count();
+            assert_eq!(accepted, alphabet.len(), "{alphabet:?}");
+        }
+        assert_eq!(Alphabet::MixedCase.priority(b'a'), Some(1));
+        assert_eq!(Alphabet::MixedCase.priority(b'Z'), Some(52));
+    }
+
+    #[test]
+    fn rejects_characters_outside_the_alphabet() {
+        assert!(CharSet::from_bytes(Alphabet::Lowercase, b"abcD").is_err());
+        assert!(CharSet::from_bytes(Alphabet::Ascii, &[b'a', 0xff]).is_err());
+        let set = CharSet::from_bytes(Alphabet::Bytes, &[0xff, 0, b'a']).unwrap();
+        assert_eq!(set.iter().collect::<Vec<_>>(), [0, b'a', 0xff]);
+    }
+
+    #[test]
+    fn set_operations() {
+        let set = |s: &str| CharSet::from_bytes(Alphabet::MixedCase, s.as_bytes()).unwrap();
+        let (a, b) = (set("abcXY"), set("bcdY"));
+        assert_eq!((a & b).to_string(), "bcY");
+        assert_eq!((a | b).to_string(), "abcdXY");
+        assert_eq!((a - b).to_string(), "aX");
+        assert_eq!((a & b).len(), 3);
+        assert_eq!((a - b).priorities().collect::<Vec<_>>(), [1, 24 + 26]);
+        assert_eq!(CharSet::full(Alphabet::Lowercase).len(), 26);
+        let mut c = CharSet::new(Alphabet::MixedCase);
+        assert!(!c.insert(b'q').unwrap());
+        assert!(c.insert(b'q').unwrap());
+    }
+
+    #[test]
+    fn parses_alphabet_names() {
+        assert_eq!("ascii".parse::<Alphabet>().unwrap(), Alphabet::Ascii);
+        assert!("utf8".parse::<Alphabet>().is_err());
+    }
+}
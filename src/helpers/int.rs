@@ -0,0 +1,144 @@
+use std::fmt::{Debug, Display};
+
+use aoc_framework::anyhow::{self, anyhow};
+
+/// Signed integer used by days whose values can outgrow 64 bits.
+/// Arbitrary-precision with the `bigint` feature, overflow-checked otherwise.
+#[cfg(not(feature = "bigint"))]
+pub type Int = i64;
+#[cfg(feature = "bigint")]
+pub type Int = num_bigint::BigInt;
+
+/// Unsigned counterpart of [`Int`]
+#[cfg(not(feature = "bigint"))]
+pub type UInt = u64;
+#[cfg(feature = "bigint")]
+pub type UInt = num_bigint::BigUint;
+
+/// Arithmetic that reports overflow, underflow and division by zero as errors
+/// naming the operation, instead of panicking or wrapping.
+pub trait Checked: Sized + Clone + Ord + Debug + Display {
+    fn from_i64(n: i64) -> anyhow::Result<Self>;
+    fn to_u64(&self) -> anyhow::Result<u64>;
+    fn is_zero(&self) -> bool;
+    fn try_add(&self, rhs: &Self) -> anyhow::Result<Self>;
+    fn try_sub(&self, rhs: &Self) -> anyhow::Result<Self>;
+    fn try_mul(&self, rhs: &Self) -> anyhow::Result<Self>;
+    fn try_div(&self, rhs: &Self) -> anyhow::Result<Self>;
+    fn try_rem(&self, rhs: &Self) -> anyhow::Result<Self>;
+    /// Remainder that is never negative, like `rem_euclid`
+    fn try_rem_euclid(&self, rhs: &Self) -> anyhow::Result<Self>;
+}
+
+fn failed(lhs: &dyn Display, op: char, rhs: &dyn Display) -> anyhow::Error {
+    anyhow!("Arithmetic overflow or division by zero computing {lhs} {op} {rhs}")
+}
+
+macro_rules! impl_checked_primitive {
+    ($($t:ty),*) => {$(
+        impl Checked for $t {
+            fn from_i64(n: i64) -> anyhow::Result<Self> {
+                Ok(n.try_into()?)
+            }
+
+            fn to_u64(&self) -> anyhow::Result<u64> {
+                Ok((*self).try_into()?)
+            }
+
+            fn is_zero(&self) -> bool {
+                *self == 0
+            }
+
+            fn try_add(&self, rhs: &Self) -> anyhow::Result<Self> {
+                self.checked_add(*rhs).ok_or_else(|| failed(self, '+', rhs))
+            }
+
+            fn try_sub(&self, rhs: &Self) -> anyhow::Result<Self> {
+                self.checked_sub(*rhs).ok_or_else(|| failed(self, '-', rhs))
+            }
+
+            fn try_mul(&self, rhs: &Self) -> anyhow::Result<Self> {
+                self.checked_mul(*rhs).ok_or_else(|| failed(self, '*', rhs))
+            }
+
+            fn try_div(&self, rhs: &Self) -> anyhow::Result<Self> {
+                self.checked_div(*rhs).ok_or_else(|| failed(self, '/', rhs))
+            }
+
+            fn try_rem(&self, rhs: &Self) -> anyhow::Result<Self> {
+                self.checked_rem(*rhs).ok_or_else(|| failed(self, '%', rhs))
+            }
+
+            fn try_rem_euclid(&self, rhs: &Self) -> anyhow::Result<Self> {
+                self.checked_rem_euclid(*rhs)
+                    .ok_or_else(|| failed(self, '%', rhs))
+            }
+        }
+    )*};
+}
+
+impl_checked_primitive!(i64, u64);
+
+#[cfg(feature = "bigint")]
+macro_rules! impl_checked_big {
+    ($($t:ty: unsigned = $unsigned:literal),*) => {$(
+        impl Checked for $t {
+            fn from_i64(n: i64) -> anyhow::Result<Self> {
+                Ok(n.try_into()?)
+            }
+
+            fn to_u64(&self) -> anyhow::Result<u64> {
+                Ok(self.try_into()?)
+            }
+
+            fn is_zero(&self) -> bool {
+                self.bits() == 0
+            }
+
+            fn try_add(&self, rhs: &Self) -> anyhow::Result<Self> {
+                Ok(self + rhs)
+            }
+
+            fn try_sub(&self, rhs: &Self) -> anyhow::Result<Self> {
+                if $unsigned && self < rhs {
+                    return Err(failed(self, '-', rhs));
+                }
+                Ok(self - rhs)
+            }
+
+            fn try_mul(&self, rhs: &Self) -> anyhow::Result<Self> {
+                Ok(self * rhs)
+            }
+
+            fn try_div(&self, rhs: &Self) -> anyhow::Result<Self> {
+                if rhs.is_zero() {
+                    return Err(failed(self, '/', rhs));
+                }
+                Ok(self / rhs)
+            }
+
+            fn try_rem(&self, rhs: &Self) -> anyhow::Result<Self> {
+                if rhs.is_zero() {
+                    return Err(failed(self, '%', rhs));
+                }
+                Ok(self % rhs)
+            }
+
+            fn try_rem_euclid(&self, rhs: &Self) -> anyhow::Result<Self> {
+                let rem = self.try_rem(rhs)?;
+                let zero = Self::default();
+                Ok(match (rem < zero, *rhs < zero) {
+                    (false, _) => rem,
+                    (true, false) => rem + rhs,
+                    (true, true) => rem - rhs,
+                })
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "bigint")]
+impl_checked_big!(
+    num_bigint::BigInt: unsigned = false,
+    num_bigint::BigUint: unsigned = true
+);
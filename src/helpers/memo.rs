@@ -0,0 +1,169 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl std::fmt::Display for MemoStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let MemoStats {
+            hits,
+            misses,
+            evictions,
+        } = self;
+        write!(f, "{hits} hits, {misses} misses, {evictions} evictions")
+    }
+}
+
+const MAX_PREALLOCATED: usize = 1 << 20;
+
+/// Cache for recursive searches.
+///
+/// When created with [`Memo::bounded`], the least recently used half of the
+/// entries is evicted whenever the cache is full, which keeps eviction cost
+/// amortized O(1) per insertion.
+pub struct Memo<K, V, S = RandomState> {
+    map: HashMap<K, (V, u64), S>,
+    capacity: Option<usize>,
+    tick: u64,
+    stats: MemoStats,
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Default for Memo<K, V, S> {
+    fn default() -> Self {
+        Memo {
+            map: HashMap::default(),
+            capacity: None,
+            tick: 0,
+            stats: MemoStats::default(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Memo<K, V, S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cache holding at most `capacity` entries. Only up to
+    /// [`MAX_PREALLOCATED`] entries are allocated upfront, so that a huge
+    /// capacity behaves like an unbounded cache instead of failing to allocate.
+    pub fn bounded(capacity: usize) -> Self {
+        let preallocated = capacity.min(MAX_PREALLOCATED);
+        Memo {
+            map: HashMap::with_capacity_and_hasher(preallocated, S::default()),
+            capacity: Some(capacity.max(2)),
+            ..Self::default()
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.tick += 1;
+        match self.map.get_mut(key) {
+            Some((value, last_used)) => {
+                self.stats.hits += 1;
+                *last_used = self.tick;
+                Some(value)
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        if matches!(self.capacity, Some(capacity) if self.map.len() >= capacity) {
+            self.evict();
+        }
+        self.tick += 1;
+        self.map.insert(key, (value, self.tick));
+    }
+
+    /// Returns the cached value for `key`, computing it with `f` on a miss.
+    /// `f` receives the cache so that it can recurse.
+    pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce(&mut Self) -> V) -> V
+    where
+        K: Clone,
+        V: Clone,
+    {
+        if let Some(value) = self.get(&key) {
+            return value.clone();
+        }
+        let value = f(self);
+        self.insert(key, value.clone());
+        value
+    }
+
+    fn evict(&mut self) {
+        let mut ticks: Vec<u64> = self.map.values().map(|&(_, tick)| tick).collect();
+        let mid = ticks.len() / 2;
+        let (_, &mut threshold, _) = ticks.select_nth_unstable(mid);
+        let before = self.map.len();
+        self.map.retain(|_, &mut (_, tick)| tick >= threshold);
+        self.stats.evictions += (before - self.map.len()) as u64;
+    }
+
+    pub fn stats(&self) -> MemoStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fib(n: u64, memo: &mut Memo<u64, u64>) -> u64 {
+        if n < 2 {
+            return n;
+        }
+        memo.get_or_insert_with(n, |memo| fib(n - 1, memo) + fib(n - 2, memo))
+    }
+
+    #[test]
+    fn unbounded_counts_hits_and_misses() {
+        let mut memo = Memo::new();
+        assert_eq!(fib(50, &mut memo), 12586269025);
+        let stats = memo.stats();
+        assert_eq!(stats.misses, 49);
+        assert_eq!(stats.hits, 47);
+        assert_eq!(stats.evictions, 0);
+    }
+
+    #[test]
+    fn bounded_evicts_least_recently_used() {
+        let mut memo: Memo<u32, u32> = Memo::bounded(4);
+        for i in 0..4 {
+            memo.insert(i, i);
+        }
+        // touch 0 and 1 so that 2 and 3 are the oldest entries
+        assert_eq!(memo.get(&0), Some(&0));
+        assert_eq!(memo.get(&1), Some(&1));
+        memo.insert(4, 4);
+        assert_eq!(memo.stats().evictions, 2);
+        assert_eq!(memo.get(&2), None);
+        assert_eq!(memo.get(&3), None);
+        for i in [0, 1, 4] {
+            assert_eq!(memo.get(&i), Some(&i));
+        }
+    }
+
+    #[test]
+    fn huge_capacity_is_not_preallocated() {
+        let mut memo: Memo<u64, u64> = Memo::bounded(usize::MAX);
+        assert_eq!(fib(50, &mut memo), 12586269025);
+        assert_eq!(memo.stats().evictions, 0);
+    }
+
+    #[test]
+    fn bounded_results_match_unbounded() {
+        let mut memo = Memo::bounded(3);
+        assert_eq!(fib(60, &mut memo), 1548008755920);
+        assert!(memo.stats().evictions > 0);
+    }
+}
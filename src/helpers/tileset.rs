@@ -0,0 +1,93 @@
+use fxhash::FxHashMap;
+
+/// Set of cells stored as 64x64 bitmap tiles, for sparse sets spread over an
+/// area far larger than the number of cells in them.
+///
+/// Memory follows the tiles that hold at least one cell rather than the
+/// bounding box. Inserting next to the previous cell reuses its tile without
+/// hashing.
+#[derive(Clone, Default, Debug)]
+pub struct TileSet {
+    tiles: Vec<[u64; 64]>,
+    index: FxHashMap<(isize, isize), usize>,
+    last: Option<((isize, isize), usize)>,
+    len: usize,
+}
+
+/// Tile of a cell, and its row and bit within the tile
+fn locate(x: isize, y: isize) -> ((isize, isize), usize, u32) {
+    let tile = (x.div_euclid(64), y.div_euclid(64));
+    (tile, y.rem_euclid(64) as usize, x.rem_euclid(64) as u32)
+}
+
+impl TileSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a cell, returning whether it was new
+    pub fn insert(&mut self, x: isize, y: isize) -> bool {
+        let (tile, row, bit) = locate(x, y);
+        let ndx = match self.last {
+            Some((last, ndx)) if last == tile => ndx,
+            _ => {
+                let next = self.tiles.len();
+                let ndx = *self.index.entry(tile).or_insert(next);
+                if ndx == next {
+                    self.tiles.push([0; 64]);
+                }
+                self.last = Some((tile, ndx));
+                ndx
+            }
+        };
+        let word = &mut self.tiles[ndx][row];
+        let new = *word & (1 << bit) == 0;
+        *word |= 1 << bit;
+        self.len += new as usize;
+        new
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_reports_new_cells() {
+        let mut set = TileSet::new();
+        let cells = [
+            (0, 0),
+            (63, 63),
+            (64, 0),
+            (-1, -1),
+            (-64, 5),
+            (1 << 40, -(1 << 40)),
+        ];
+        for &(x, y) in &cells {
+            assert!(set.insert(x, y), "({x}, {y}) was already there");
+        }
+        for &(x, y) in &cells {
+            assert!(!set.insert(x, y), "missing ({x}, {y})");
+        }
+        assert_eq!(set.len(), cells.len());
+        // neighbours across tile borders are separate cells
+        assert!(set.insert(-65, 5));
+        assert!(set.insert(0, -1));
+        assert_eq!(set.len(), cells.len() + 2);
+    }
+
+    #[test]
+    fn tiles_follow_the_cells() {
+        let mut set = TileSet::new();
+        for x in 0..1000 {
+            set.insert(x, x);
+        }
+        assert_eq!(set.len(), 1000);
+        // a diagonal crosses one tile per 64 cells
+        assert_eq!(set.tiles.len(), 1000usize.div_ceil(64));
+    }
+}
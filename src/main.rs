@@ -17,8 +17,17 @@ mod day12;
 mod day13;
 mod day14;
 mod day15;
+mod day16;
+mod day21;
+mod day23;
+mod day25;
 
 fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some((day, args)) = args.split_first() {
+        return explore(day, args);
+    }
+
     let token = std::env::var("AOC_TOKEN")?;
 
     day01::Day1::run(&token);
@@ -36,6 +45,37 @@ fn main() -> anyhow::Result<()> {
     day13::Day13::run(&token);
     day14::Day14::run(&token);
     day15::Day15::run(&token);
+    day16::Day16::run(&token);
+    day21::Day21::run(&token);
+    day23::Day23::run(&token);
+    day25::Day25::run(&token);
+
+    Ok(())
+}
 
+/// Runs one of a day's exploration commands on the input read from stdin,
+/// e.g. `aoc-2022 1 top 5 < input`
+fn explore(day: &str, args: &[String]) -> anyhow::Result<()> {
+    let input = std::io::stdin()
+        .lines()
+        .collect::<std::io::Result<Vec<_>>>()?
+        .into_iter();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = match day.parse::<u8>()? {
+        1 => day01::explore(&args, input)?,
+        2 => day02::explore(&args, input)?,
+        3 => day03::explore(&args, input)?,
+        4 => day04::explore(&args, input)?,
+        5 => day05::explore(&args, input)?,
+        7 => day07::explore(&args, input)?,
+        8 => day08::explore(&args, input)?,
+        9 => day09::explore(&args, input)?,
+        10 => day10::explore(&args, input)?,
+        11 => day11::explore(&args, input)?,
+        12 => day12::explore(&args, input)?,
+        16 => day16::explore(&args, input)?,
+        _ => anyhow::bail!("Day {day} has no exploration commands"),
+    };
+    print!("{output}");
     Ok(())
 }